@@ -0,0 +1,380 @@
+use crate::duplicates::Duplicates;
+use crate::entry::Entry;
+use crate::exec::{Batch, Exec};
+use crate::format::{self, path_bytes, Format};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Stdout, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// An operation performed on an entry for its side effects.
+#[derive(Debug)]
+pub enum Action {
+    /// `-print`
+    Print,
+    /// `-print0`: print the path followed by a NUL rather than a newline
+    Print0,
+    /// `-printf`
+    Printf(Format),
+    /// `-fprint`: print the path to a file, which is truncated first
+    FPrint(File),
+    /// `-ls`: print a line like `ls -dils`, with times measured from `now`
+    Ls { now: SystemTime },
+    /// `-json`: print a JSON object describing the entry on a line of its own
+    Json,
+    /// `-duplicates`: keep a regular file to look for others with the same content
+    Duplicates(Arc<Duplicates>),
+    /// `-prune`: do not descend into the directory
+    Prune,
+    /// `-exec`, `-execdir`, `-ok`, `-okdir`
+    Exec(Exec),
+    /// `-delete`: remove the file or empty directory, never what a link points to
+    Delete,
+}
+
+/// State shared by the actions while evaluating the expression.
+#[derive(Debug)]
+pub struct Context<W: Write = BufWriter<Stdout>> {
+    /// Where `-print` and friends write; flushed before a command writes too
+    pub output: W,
+    /// The first error writing the output, which ends the walk
+    output_error: Option<io::Error>,
+    /// Set by `-prune` for the entry being evaluated
+    pub prune: bool,
+    /// Paths waiting for each `-exec ... {} +` to run, by the action's id
+    batches: BTreeMap<usize, Batch>,
+    /// Set when a command or the walk failed, for an exit status of 1
+    failed: bool,
+}
+
+impl Default for Context {
+    /// A context writing to buffered standard output.
+    fn default() -> Context {
+        Context::new(BufWriter::new(io::stdout()))
+    }
+}
+
+impl<W: Write> Context<W> {
+    pub fn new(output: W) -> Context<W> {
+        Context {
+            output,
+            output_error: None,
+            prune: false,
+            batches: BTreeMap::new(),
+            failed: false,
+        }
+    }
+
+    /// Whether a command or the walk failed, for an exit status of 1.
+    pub fn failed(&self) -> bool {
+        self.failed
+    }
+
+    /// Notes that a command or the walk failed.
+    pub(crate) fn fail(&mut self) {
+        self.failed = true;
+    }
+
+    /// The first error writing the output, if there was one since the last call.
+    pub fn take_output_error(&mut self) -> Option<io::Error> {
+        self.output_error.take()
+    }
+
+    /// The pending batches of `-exec ... {} +`, by the action's id.
+    pub(crate) fn batches(&mut self) -> &mut BTreeMap<usize, Batch> {
+        &mut self.batches
+    }
+
+    /// Writes to the output, keeping the first error.
+    pub fn write(&mut self, bytes: &[u8]) {
+        if self.output_error.is_none() {
+            if let Err(e) = self.output.write_all(bytes) {
+                self.output_error = Some(e);
+            }
+        }
+    }
+
+    /// Writes out what is buffered, before a command writes its own output.
+    pub fn flush_output(&mut self) {
+        if let Err(e) = self.output.flush() {
+            self.output_error.get_or_insert(e);
+        }
+    }
+
+    /// Runs the pending batch of the action `id`.
+    pub(crate) fn run_batch(&mut self, id: usize) {
+        self.flush_output();
+        if let Some(batch) = self.batches.get_mut(&id) {
+            self.failed |= !batch.run();
+        }
+    }
+
+    /// Runs every pending batch, once the walk is done.
+    pub fn finish(&mut self) {
+        let ids: Vec<usize> = self.batches.keys().copied().collect();
+        for id in ids {
+            self.run_batch(id);
+        }
+        self.flush_output();
+    }
+}
+
+impl Action {
+    pub fn eval<W: Write>(&self, entry: &Entry, ctx: &mut Context<W>) -> bool {
+        match self {
+            Action::Print => {
+                ctx.write(&path_bytes(entry.path()));
+                ctx.write(b"\n");
+                true
+            }
+            Action::Print0 => {
+                ctx.write(&path_bytes(entry.path()));
+                ctx.write(b"\0");
+                true
+            }
+            Action::Printf(format) => {
+                ctx.write(&format.render(entry));
+                true
+            }
+            Action::Ls { now } => {
+                ctx.write(format::render_ls(entry, *now).as_bytes());
+                true
+            }
+            Action::Json => {
+                ctx.write(format::render_json(entry).as_bytes());
+                true
+            }
+            Action::Duplicates(duplicates) => {
+                duplicates.add(entry);
+                true
+            }
+            Action::FPrint(file) => {
+                let mut line = path_bytes(entry.path()).into_owned();
+                line.push(b'\n');
+                if let Err(e) = (&*file).write_all(&line) {
+                    crate::report(e);
+                    ctx.fail();
+                }
+                true
+            }
+            Action::Prune => {
+                ctx.prune = true;
+                true
+            }
+            Action::Exec(exec) => exec.eval(entry.path(), ctx),
+            Action::Delete => {
+                let path = entry.path();
+                // Like GNU find, leave the current directory in place.
+                if path == Path::new(".") {
+                    return true;
+                }
+                // Decided from the entry itself, so that a link is removed
+                // rather than what it points to, even with -L.
+                let is_dir = fs::symlink_metadata(path).is_ok_and(|m| m.is_dir());
+                let removed = match is_dir {
+                    true => fs::remove_dir(path),
+                    false => fs::remove_file(path),
+                };
+                match removed {
+                    Ok(()) => true,
+                    Err(e) => {
+                        crate::report(format_args!("cannot delete '{}': {}", path.display(), e));
+                        ctx.fail();
+                        false
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Action, Context};
+    use crate::duplicates::Duplicates;
+    use crate::entry::Entry;
+    use crate::expr::{parse_command, Expr};
+    use crate::format::Format;
+    use crate::test_fixture::{entry, fixture};
+    use std::fs::{self, File};
+    use std::sync::Arc;
+    use std::time::SystemTime;
+
+    /// What evaluating `action` for each of `entries` writes to the output.
+    fn output(action: &Action, entries: &[Entry]) -> String {
+        let mut ctx = Context::new(Vec::new());
+        for entry in entries {
+            assert!(action.eval(entry, &mut ctx));
+        }
+        String::from_utf8(ctx.output).unwrap()
+    }
+
+    #[test]
+    fn test_print() {
+        let tmp = fixture();
+        let (file, dir) = (
+            entry(tmp.path().join("file")),
+            entry(tmp.path().join("dir")),
+        );
+        let root = tmp.path().display();
+        assert_eq!(
+            output(&Action::Print, &[file.clone(), dir.clone()]),
+            format!("{root}/file\n{root}/dir\n")
+        );
+        assert_eq!(
+            output(&Action::Print0, &[file, dir]),
+            format!("{root}/file\0{root}/dir\0")
+        );
+    }
+
+    #[test]
+    fn test_printf() {
+        let tmp = fixture();
+        let format = Format::parse("%f %y %s %d\\n").unwrap();
+        assert_eq!(
+            output(&Action::Printf(format), &[entry(tmp.path().join("file"))]),
+            "file f 1 1\n"
+        );
+    }
+
+    #[test]
+    fn test_ls_json() {
+        let tmp = fixture();
+        let path = tmp.path().join("file");
+        let ls = output(
+            &Action::Ls {
+                now: SystemTime::now(),
+            },
+            &[entry(&path)],
+        );
+        assert!(ls.ends_with(&format!(" {}\n", path.display())), "{}", ls);
+        let json = output(&Action::Json, &[entry(&path)]);
+        assert!(json.starts_with(&format!(
+            "{{\"path\":\"{}\",\"type\":\"file\"",
+            path.display()
+        )));
+        assert!(json.ends_with("}\n"));
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let tmp = fixture();
+        fs::write(tmp.path().join("copy"), "x").unwrap();
+        let duplicates = Arc::new(Duplicates::new(false));
+        let action = Action::Duplicates(Arc::clone(&duplicates));
+        let entries = ["file", "dir", "copy"].map(|name| entry(tmp.path().join(name)));
+        assert_eq!(output(&action, &entries), "");
+        let (sets, readable) = duplicates.sets();
+        assert!(readable);
+        assert_eq!(sets.len(), 1);
+        assert_eq!(
+            sets[0].paths,
+            [tmp.path().join("copy"), tmp.path().join("file")]
+        );
+    }
+
+    #[test]
+    fn test_fprint() {
+        let tmp = fixture();
+        let out = tmp.path().join("out");
+        let action = Action::FPrint(File::create(&out).unwrap());
+        let file = entry(tmp.path().join("file"));
+        // Nothing goes to the output of the context.
+        assert_eq!(output(&action, &[file.clone(), file]), "");
+        let path = tmp.path().join("file");
+        assert_eq!(
+            fs::read_to_string(out).unwrap(),
+            format!("{}\n{}\n", path.display(), path.display())
+        );
+    }
+
+    #[test]
+    fn test_prune() {
+        let tmp = fixture();
+        let mut ctx = Context::new(Vec::new());
+        assert!(!ctx.prune);
+        assert!(Action::Prune.eval(&entry(tmp.path().join("dir")), &mut ctx));
+        assert!(ctx.prune);
+        assert!(ctx.output.is_empty());
+    }
+
+    #[test]
+    fn test_delete() {
+        let tmp = fixture();
+        fs::write(tmp.path().join("dir/inner"), "").unwrap();
+        let mut ctx = Context::new(Vec::new());
+
+        // A directory that is not empty stays, and the failure is noted.
+        assert!(!Action::Delete.eval(&entry(tmp.path().join("dir")), &mut ctx));
+        assert!(ctx.failed());
+
+        let mut ctx = Context::new(Vec::new());
+        for name in ["file", "dir/inner", "dir"] {
+            assert!(Action::Delete.eval(&entry(tmp.path().join(name)), &mut ctx));
+            assert!(!tmp.path().join(name).exists());
+        }
+        assert!(!ctx.failed());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_delete_link() {
+        let tmp = fixture();
+        let link = tmp.path().join("link");
+        std::os::unix::fs::symlink("file", &link).unwrap();
+        // Even followed, the link is removed rather than what it points to.
+        let followed = Entry::new(link.clone(), 1, true).unwrap();
+        let mut ctx = Context::new(Vec::new());
+        assert!(Action::Delete.eval(&followed, &mut ctx));
+        assert!(fs::symlink_metadata(link).is_err());
+        assert!(tmp.path().join("file").exists());
+    }
+
+    /// The expression of `find_rs ARGS`, with its actions numbered by the parser.
+    fn parse(args: &[&str]) -> Expr {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        parse_command(&args).unwrap().expr
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_exec() {
+        let tmp = fixture();
+        let file = entry(tmp.path().join("file"));
+        let mut ctx = Context::new(Vec::new());
+        assert!(parse(&["-exec", "test", "-f", "{}", ";"]).eval(&file, &mut ctx));
+        assert!(!parse(&["-exec", "test", "-d", "{}", ";"]).eval(&file, &mut ctx));
+        assert!(!ctx.failed());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_exec_batch() {
+        let tmp = fixture();
+        let log = tmp.path().join("log");
+        // Appends a line per run with the number of paths it was given.
+        let script = format!("echo $# >> '{}'", log.display());
+        let expr = parse(&[
+            "-exec", "sh", "-c", &script, "sh", "{}", "+", "-exec", "true", "{}", "+",
+        ]);
+        let mut ctx = Context::new(Vec::new());
+        for name in ["file", "dir", "file"] {
+            assert!(expr.eval(&entry(tmp.path().join(name)), &mut ctx));
+        }
+        // Nothing has run until the walk is done, and each command has its own batch.
+        assert!(!log.exists());
+        assert_eq!(ctx.batches().len(), 2);
+        ctx.finish();
+        assert_eq!(fs::read_to_string(&log).unwrap(), "3\n");
+        assert!(!ctx.failed());
+
+        // A failing batch is reported through the context.
+        let mut ctx = Context::new(Vec::new());
+        let expr = parse(&["-exec", "false", "{}", "+"]);
+        assert!(expr.eval(&entry(tmp.path().join("file")), &mut ctx));
+        ctx.finish();
+        assert!(ctx.failed());
+    }
+}
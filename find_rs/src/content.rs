@@ -0,0 +1,171 @@
+use crate::entry::Entry;
+use regex::bytes::Regex;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+
+/// How many bytes from the start of a file `-mime` looks at.
+const SNIFF_LEN: usize = 1024;
+
+/// How many bytes of a line `-contains` tests.
+const MAX_LINE: u64 = 1 << 20;
+
+/// Signatures at the start of a file, or at an offset, and the type they mark.
+const MAGIC: &[(usize, &[u8], &str)] = &[
+    (0, b"\x89PNG\r\n\x1a\n", "image/png"),
+    (0, b"\xff\xd8\xff", "image/jpeg"),
+    (0, b"GIF87a", "image/gif"),
+    (0, b"GIF89a", "image/gif"),
+    (0, b"%PDF-", "application/pdf"),
+    (0, b"PK\x03\x04", "application/zip"),
+    (0, b"\x1f\x8b", "application/gzip"),
+    (0, b"BZh", "application/x-bzip2"),
+    (0, b"\xfd7zXZ\x00", "application/x-xz"),
+    (0, b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+    (0, b"\x28\xb5\x2f\xfd", "application/zstd"),
+    (257, b"ustar", "application/x-tar"),
+    (0, b"\x7fELF", "application/x-executable"),
+    (0, b"\x00asm", "application/wasm"),
+    (0, b"SQLite format 3\x00", "application/vnd.sqlite3"),
+    (0, b"OggS", "audio/ogg"),
+    (0, b"fLaC", "audio/flac"),
+    (0, b"ID3", "audio/mpeg"),
+    (4, b"ftyp", "video/mp4"),
+];
+
+/// `-contains`: whether a line of the regular file at `path` matches `re`.
+/// A file that cannot be read matches nothing. A line longer than
+/// `MAX_LINE` bytes is tested on its first `MAX_LINE` bytes, so that a file
+/// without newlines is not read into memory whole.
+pub fn contains(path: &Path, re: &Regex) -> bool {
+    let Ok(file) = File::open(path) else {
+        return false;
+    };
+    let mut reader = BufReader::new(file);
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        match (&mut reader).take(MAX_LINE).read_until(b'\n', &mut line) {
+            Ok(0) | Err(_) => return false,
+            Ok(_) => {}
+        }
+        let text = line.strip_suffix(b"\n");
+        if re.is_match(text.unwrap_or(&line)) {
+            return true;
+        }
+        if text.is_none() && skip_line(&mut reader).is_err() {
+            return false;
+        }
+    }
+}
+
+/// Reads past the rest of a line without keeping it.
+fn skip_line(reader: &mut impl BufRead) -> io::Result<()> {
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(());
+        }
+        match buf.iter().position(|&b| b == b'\n') {
+            Some(newline) => {
+                reader.consume(newline + 1);
+                return Ok(());
+            }
+            None => {
+                let len = buf.len();
+                reader.consume(len);
+            }
+        }
+    }
+}
+
+/// The media type of an entry as `file --mime-type` names it: from the
+/// first bytes of a regular file, `text/plain` for other UTF-8 without NUL
+/// bytes, and `inode/...` for what is not a regular file.
+pub fn mime_type(entry: &Entry) -> &'static str {
+    let file_type = entry.file_type();
+    if !file_type.is_file() {
+        return match crate::stat::type_char(file_type) {
+            'd' => "inode/directory",
+            'l' => "inode/symlink",
+            'b' => "inode/blockdevice",
+            'c' => "inode/chardevice",
+            'p' => "inode/fifo",
+            's' => "inode/socket",
+            _ => "application/octet-stream",
+        };
+    }
+    let mut head = Vec::with_capacity(SNIFF_LEN);
+    let read = File::open(entry.path())
+        .and_then(|file| file.take(SNIFF_LEN as u64).read_to_end(&mut head));
+    match read {
+        Ok(0) => "inode/x-empty",
+        Ok(_) => sniff(&head),
+        Err(_) => "application/octet-stream",
+    }
+}
+
+/// The media type of a file that starts with `head`.
+fn sniff(head: &[u8]) -> &'static str {
+    let magic = MAGIC.iter().find(|(offset, signature, _)| {
+        head.get(*offset..offset + signature.len()) == Some(signature)
+    });
+    if let Some((_, _, mime)) = magic {
+        return mime;
+    }
+    if head.starts_with(b"RIFF") && head.get(8..12) == Some(b"WEBP") {
+        return "image/webp";
+    }
+    if head.contains(&0) {
+        return "application/octet-stream";
+    }
+    // A character may be cut off at the end of what was read.
+    let valid = match std::str::from_utf8(head) {
+        Ok(_) => head.len(),
+        Err(e) if e.error_len().is_none() => e.valid_up_to(),
+        Err(_) => return "application/octet-stream",
+    };
+    let text = String::from_utf8_lossy(&head[..valid]);
+    let start = text.trim_start().to_ascii_lowercase();
+    if start.starts_with("<!doctype html") || start.starts_with("<html") {
+        "text/html"
+    } else if start.starts_with("<?xml") {
+        "text/xml"
+    } else {
+        "text/plain"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{contains, sniff, MAX_LINE};
+    use regex::bytes::Regex;
+    use std::fs;
+
+    #[test]
+    fn test_sniff() {
+        assert_eq!(sniff(b"\x89PNG\r\n\x1a\n\x00\x00"), "image/png");
+        assert_eq!(sniff(b"%PDF-1.7\n"), "application/pdf");
+        assert_eq!(sniff(b"RIFF\x00\x00\x00\x00WEBPVP8 "), "image/webp");
+        assert_eq!(sniff(b"\x00\x00\x00\x18ftypmp42"), "video/mp4");
+        assert_eq!(sniff(b"plain text\n"), "text/plain");
+        assert_eq!(sniff("caf\u{e9}".as_bytes()[..4].as_ref()), "text/plain");
+        assert_eq!(sniff(b"  <!DOCTYPE html>"), "text/html");
+        assert_eq!(sniff(b"text\x00binary"), "application/octet-stream");
+        assert_eq!(sniff(b"\xff\xfe\xfd"), "application/octet-stream");
+    }
+
+    #[test]
+    fn test_contains_long_line() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("long");
+        let mut text = vec![b'x'; MAX_LINE as usize + 10];
+        text.extend_from_slice(b"late\nnext\n");
+        fs::write(&path, text).unwrap();
+        let re = |pattern| Regex::new(pattern).unwrap();
+        assert!(contains(&path, &re("^x+")));
+        // Past the bytes of the long line that are tested.
+        assert!(!contains(&path, &re("late")));
+        assert!(contains(&path, &re("^next$")));
+    }
+}
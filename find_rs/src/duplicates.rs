@@ -0,0 +1,195 @@
+use crate::entry::Entry;
+use crate::format::json_string;
+use crate::stat;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashMap};
+use std::fs::File;
+use std::hash::Hash;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// How many bytes from the start of a file are hashed before the rest.
+const PARTIAL_LEN: u64 = 4096;
+
+/// `-duplicates`: the regular files the expression reached, to be grouped
+/// by content once the walk is done.
+#[derive(Debug)]
+pub struct Duplicates {
+    /// Whether the sets are printed as JSON
+    json: bool,
+    /// The size and path of each file, with the path that sorts first where
+    /// several lead to it, as hard links and followed links do
+    files: Mutex<HashMap<FileId, (u64, PathBuf)>>,
+}
+
+/// What tells files apart: the device and inode where the platform has them.
+#[derive(Debug, PartialEq, Eq, Hash)]
+enum FileId {
+    Inode(u64, u64),
+    Path(PathBuf),
+}
+
+/// Files with the same content.
+#[derive(Debug, PartialEq)]
+pub struct DuplicateSet {
+    pub size: u64,
+    /// SHA-256 of the content, in hex
+    pub hash: String,
+    pub paths: Vec<PathBuf>,
+}
+
+impl Duplicates {
+    pub fn new(json: bool) -> Duplicates {
+        Duplicates {
+            json,
+            files: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Records `entry` if it is a regular file that is not empty, once for
+    /// all the paths that lead to the same file.
+    pub fn add(&self, entry: &Entry) {
+        if !entry.file_type().is_file() {
+            return;
+        }
+        let Some(metadata) = entry.metadata().ok().filter(|m| m.len() > 0) else {
+            return;
+        };
+        let path = entry.path().to_path_buf();
+        let id = match stat::inode(&metadata) {
+            Some((dev, ino)) => FileId::Inode(dev, ino),
+            None => FileId::Path(path.clone()),
+        };
+        let mut files = self.files.lock().unwrap();
+        let (_, first) = files.entry(id).or_insert((metadata.len(), path.clone()));
+        if path < *first {
+            *first = path;
+        }
+    }
+
+    /// Groups the files by size, then by a hash of their first bytes, then
+    /// by a hash of their whole content, reading each file only as far as
+    /// needed. A file that cannot be read is reported and left out, making
+    /// the second value false. The sets are ordered largest first.
+    pub fn sets(&self) -> (Vec<DuplicateSet>, bool) {
+        let files: BTreeSet<(u64, PathBuf)> = std::mem::take(&mut *self.files.lock().unwrap())
+            .into_values()
+            .collect();
+        let mut readable = true;
+        let mut hash = |path: &PathBuf, limit: Option<u64>| match hash_file(path, limit) {
+            Ok(hash) => Some(hash),
+            Err(e) => {
+                crate::report(format_args!("{}: {}", path.display(), e));
+                readable = false;
+                None
+            }
+        };
+
+        let mut sets = Vec::new();
+        for (size, paths) in group(files, |(size, _)| Some(*size)) {
+            let paths: Vec<PathBuf> = paths.into_iter().map(|(_, path)| path).collect();
+            let candidates = match size > PARTIAL_LEN {
+                true => group(paths, |path| hash(path, Some(PARTIAL_LEN)))
+                    .into_iter()
+                    .map(|(_, paths)| paths)
+                    .collect(),
+                false => vec![paths],
+            };
+            for paths in candidates {
+                for (hash, paths) in group(paths, |path| hash(path, None)) {
+                    sets.push(DuplicateSet { size, hash, paths });
+                }
+            }
+        }
+        sets.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.paths.cmp(&b.paths)));
+        (sets, readable)
+    }
+
+    /// Prints the sets: their paths a line each with a blank line between
+    /// sets, or a JSON object per set with its size, hash and paths.
+    pub fn report(&self, sets: &[DuplicateSet], out: &mut impl Write) -> io::Result<()> {
+        for (i, set) in sets.iter().enumerate() {
+            if self.json {
+                let paths: Vec<String> = set
+                    .paths
+                    .iter()
+                    .map(|path| json_string(&path.to_string_lossy()))
+                    .collect();
+                writeln!(
+                    out,
+                    "{{\"size\":{},\"sha256\":\"{}\",\"paths\":[{}]}}",
+                    set.size,
+                    set.hash,
+                    paths.join(",")
+                )?;
+            } else {
+                if i > 0 {
+                    writeln!(out)?;
+                }
+                for path in &set.paths {
+                    writeln!(out, "{}", path.display())?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Splits `items` by `key`, keeping the groups of more than one in the
+/// order of their first items. Items without a key are dropped.
+fn group<T, K: Hash + Eq + Clone>(
+    items: impl IntoIterator<Item = T>,
+    mut key: impl FnMut(&T) -> Option<K>,
+) -> Vec<(K, Vec<T>)> {
+    let mut order = Vec::new();
+    let mut groups: HashMap<K, Vec<T>> = HashMap::new();
+    for item in items {
+        let Some(key) = key(&item) else {
+            continue;
+        };
+        if !groups.contains_key(&key) {
+            order.push(key.clone());
+        }
+        groups.entry(key).or_default().push(item);
+    }
+    order
+        .into_iter()
+        .filter_map(|key| groups.remove_entry(&key))
+        .filter(|(_, items)| items.len() > 1)
+        .collect()
+}
+
+/// SHA-256 of the file at `path`, in hex, or of its first `limit` bytes.
+fn hash_file(path: &Path, limit: Option<u64>) -> io::Result<String> {
+    let mut reader = File::open(path)?.take(limit.unwrap_or(u64::MAX));
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        match reader.read(&mut buf)? {
+            0 => break,
+            n => hasher.update(&buf[..n]),
+        }
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::group;
+
+    #[test]
+    fn test_group() {
+        let groups = group([3, 1, 4, 1, 5, 9, 2, 6, 5, 3], |n| {
+            (*n != 9).then_some(n % 4)
+        });
+        assert_eq!(
+            groups,
+            [(3, vec![3, 3]), (1, vec![1, 1, 5, 5]), (2, vec![2, 6])]
+        );
+    }
+}
@@ -0,0 +1,125 @@
+use std::ffi::OsStr;
+use std::fs::{self, FileType, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+use walkdir::DirEntry;
+
+/// Which symbolic links the walk follows: `-P`, `-H` or `-L`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Follow {
+    /// `-P`: never; a link is an entry of its own
+    #[default]
+    Never,
+    /// `-H`: only links given as starting points
+    CommandLine,
+    /// `-L`: all of them; only a broken link is still a link
+    Always,
+}
+
+impl Follow {
+    /// Whether a link at `depth` is followed.
+    pub fn at_depth(self, depth: usize) -> bool {
+        match self {
+            Follow::Never => false,
+            Follow::CommandLine => depth == 0,
+            Follow::Always => true,
+        }
+    }
+}
+
+/// A file found by the walk, as the expression sees it.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    path: PathBuf,
+    depth: usize,
+    file_type: FileType,
+    /// Whether the type and metadata are those of what a link points to
+    follow: bool,
+}
+
+impl Entry {
+    /// Reads the type of `path`. When following links, a broken link is
+    /// described by the link itself.
+    pub fn new(path: PathBuf, depth: usize, follow: bool) -> io::Result<Entry> {
+        let metadata = match follow {
+            true => fs::metadata(&path).or_else(|_| fs::symlink_metadata(&path))?,
+            false => fs::symlink_metadata(&path)?,
+        };
+        Ok(Entry {
+            path,
+            depth,
+            file_type: metadata.file_type(),
+            follow,
+        })
+    }
+
+    /// Converts an entry of walkdir, which has already followed the links of
+    /// `-L` but reports a starting point given with `-H` as a link.
+    pub fn from_walkdir(entry: DirEntry, follow: Follow) -> Entry {
+        let follow = follow.at_depth(entry.depth());
+        let file_type = match entry.file_type().is_symlink() && follow {
+            true => fs::metadata(entry.path()).map_or(entry.file_type(), |m| m.file_type()),
+            false => entry.file_type(),
+        };
+        Entry {
+            depth: entry.depth(),
+            path: entry.into_path(),
+            file_type,
+            follow,
+        }
+    }
+
+    /// Converts an entry read from a directory at `depth`, using the type
+    /// the directory reports unless a link there is followed.
+    pub fn from_dir_entry(entry: &fs::DirEntry, depth: usize, follow: Follow) -> io::Result<Entry> {
+        let follow = follow.at_depth(depth);
+        let mut file_type = entry.file_type()?;
+        if file_type.is_symlink() && follow {
+            file_type = fs::metadata(entry.path()).map_or(file_type, |m| m.file_type());
+        }
+        Ok(Entry {
+            path: entry.path(),
+            depth,
+            file_type,
+            follow,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The last component of the path, or the whole path if it has none.
+    pub fn file_name(&self) -> &OsStr {
+        self.path.file_name().unwrap_or(self.path.as_os_str())
+    }
+
+    /// How far below its starting point the entry is.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn file_type(&self) -> FileType {
+        self.file_type
+    }
+
+    /// The type as the other of `-P` and `-L` sees it, for `-xtype`: with
+    /// `-P` the type of what a link points to, or a link if it is broken;
+    /// with `-L` the type of the entry itself.
+    pub fn xtype(&self) -> FileType {
+        let metadata = match (self.follow, self.file_type.is_symlink()) {
+            (true, _) => fs::symlink_metadata(&self.path),
+            (false, true) => fs::metadata(&self.path),
+            (false, false) => return self.file_type,
+        };
+        metadata.map_or(self.file_type, |m| m.file_type())
+    }
+
+    /// The metadata of the entry, or of what it links to when following.
+    pub fn metadata(&self) -> io::Result<Metadata> {
+        match self.follow {
+            true => fs::metadata(&self.path).or_else(|_| fs::symlink_metadata(&self.path)),
+            false => fs::symlink_metadata(&self.path),
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use crate::action::Context;
+use std::ffi::{OsStr, OsString};
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+/// `-exec`, `-execdir`, `-ok` and `-okdir`: run a command for entries.
+#[derive(Debug)]
+pub struct Exec {
+    /// Distinguishes the pending batch of this action from those of others;
+    /// the parser numbers the actions of a command line
+    pub(crate) id: usize,
+    /// The command and its arguments; with `batch`, the final `{}` is left out
+    pub command: Vec<String>,
+    /// `{} +`: run the command once for as many paths as fit on a command line
+    pub batch: bool,
+    /// `-execdir`, `-okdir`: run from the entry's directory with `./NAME` for `{}`
+    pub in_dir: bool,
+    /// `-ok`, `-okdir`: ask on standard error before running the command
+    pub prompt: bool,
+}
+
+/// Paths waiting for a batched command to run.
+#[derive(Debug, Default)]
+pub(crate) struct Batch {
+    command: Vec<String>,
+    dir: Option<PathBuf>,
+    paths: Vec<OsString>,
+    /// Bytes the command line takes so far
+    size: usize,
+}
+
+impl Exec {
+    /// Runs the command for `path`, or adds it to the batch. The value is the
+    /// success of the command; a batched command is always true, and a failure
+    /// fails the context instead once it runs.
+    pub fn eval<W: Write>(&self, path: &Path, ctx: &mut Context<W>) -> bool {
+        let (dir, arg) = match self.in_dir {
+            true => split_dir(path),
+            false => (None, path.as_os_str().to_owned()),
+        };
+
+        if self.batch {
+            let cost = arg_cost(&arg);
+            let full = ctx.batches().get(&self.id).is_some_and(|batch| {
+                !batch.paths.is_empty() && (batch.dir != dir || batch.size + cost > arg_limit())
+            });
+            if full {
+                ctx.run_batch(self.id);
+            }
+            let batch = ctx.batches().entry(self.id).or_default();
+            if batch.paths.is_empty() {
+                batch.command = self.command.clone();
+                batch.size = self.command.iter().map(|arg| arg_cost(arg.as_ref())).sum();
+                batch.dir = dir;
+            }
+            batch.paths.push(arg);
+            batch.size += cost;
+            return true;
+        }
+
+        let argv: Vec<OsString> = self
+            .command
+            .iter()
+            .map(|word| replace_braces(word, &arg))
+            .collect();
+        ctx.flush_output();
+        if self.prompt && !confirm(&argv) {
+            return false;
+        }
+        let mut command = Command::new(&argv[0]);
+        command.args(&argv[1..]);
+        if let Some(dir) = &dir {
+            command.current_dir(dir);
+        }
+        if self.prompt {
+            command.stdin(Stdio::null());
+        }
+        match command.status() {
+            Ok(status) => status.success(),
+            Err(e) => {
+                crate::report(format_args!("{}: {}", argv[0].to_string_lossy(), e));
+                ctx.fail();
+                false
+            }
+        }
+    }
+}
+
+impl Batch {
+    /// Runs the command for the pending paths, returning whether it succeeded.
+    pub fn run(&mut self) -> bool {
+        let paths = std::mem::take(&mut self.paths);
+        if paths.is_empty() {
+            return true;
+        }
+        let mut command = Command::new(&self.command[0]);
+        command.args(&self.command[1..]).args(paths);
+        if let Some(dir) = &self.dir {
+            command.current_dir(dir);
+        }
+        match command.status() {
+            Ok(status) => status.success(),
+            Err(e) => {
+                crate::report(format_args!("{}: {}", self.command[0], e));
+                false
+            }
+        }
+    }
+}
+
+/// The directory to run `-execdir` from and the name to pass for `path`.
+fn split_dir(path: &Path) -> (Option<PathBuf>, OsString) {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let mut name = OsString::from("./");
+    name.push(path.file_name().unwrap_or(path.as_os_str()));
+    (Some(dir), name)
+}
+
+/// Replaces every `{}` in `word` with `path`.
+fn replace_braces(word: &str, path: &OsStr) -> OsString {
+    let mut replaced = OsString::new();
+    let mut parts = word.split("{}");
+    if let Some(first) = parts.next() {
+        replaced.push(first);
+    }
+    for part in parts {
+        replaced.push(path);
+        replaced.push(part);
+    }
+    replaced
+}
+
+/// Asks whether to run `argv`, as `< CMD ARGS... > ? `; only an answer
+/// starting with `y` or `Y` agrees.
+fn confirm(argv: &[OsString]) -> bool {
+    let words: Vec<_> = argv.iter().map(|arg| arg.to_string_lossy()).collect();
+    eprint!("< {} > ? ", words.join(" "));
+    io::stderr().flush().ok();
+    let mut answer = String::new();
+    match io::stdin().lock().read_line(&mut answer) {
+        Ok(_) => answer.starts_with(['y', 'Y']),
+        Err(_) => false,
+    }
+}
+
+/// The space an argument takes on the command line: its bytes, the
+/// terminating NUL and the pointer to it.
+fn arg_cost(arg: &OsStr) -> usize {
+    arg.len() + 1 + std::mem::size_of::<usize>()
+}
+
+/// How many bytes of arguments a command line may take: ARG_MAX less the
+/// environment and some headroom, as xargs(1) computes it.
+fn arg_limit() -> usize {
+    static LIMIT: OnceLock<usize> = OnceLock::new();
+    *LIMIT.get_or_init(|| {
+        let env: usize = std::env::vars_os()
+            .map(|(key, value)| arg_cost(&key) + value.len() + 1)
+            .sum();
+        arg_max().saturating_sub(env).saturating_sub(2048)
+    })
+}
+
+#[cfg(unix)]
+fn arg_max() -> usize {
+    match unsafe { libc::sysconf(libc::_SC_ARG_MAX) } {
+        max if max > 0 => max as usize,
+        _ => 128 * 1024,
+    }
+}
+
+/// The limit of the command line on Windows, in UTF-16 units.
+#[cfg(not(unix))]
+fn arg_max() -> usize {
+    32 * 1024
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{replace_braces, split_dir};
+    use std::ffi::OsStr;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn test_replace_braces() {
+        let path = OsStr::new("a/b");
+        assert_eq!(replace_braces("{}", path), "a/b");
+        assert_eq!(replace_braces("x{}y{}", path), "xa/bya/b");
+        assert_eq!(replace_braces("{", path), "{");
+    }
+
+    #[test]
+    fn test_split_dir() {
+        assert_eq!(
+            split_dir(Path::new("a/b/c")),
+            (Some(PathBuf::from("a/b")), "./c".into())
+        );
+        assert_eq!(
+            split_dir(Path::new("a")),
+            (Some(PathBuf::from(".")), "./a".into())
+        );
+    }
+}
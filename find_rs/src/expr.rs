@@ -0,0 +1,775 @@
+use crate::action::{Action, Context};
+use crate::duplicates::Duplicates;
+use crate::entry::{Entry, Follow};
+use crate::exec::Exec;
+use crate::format::Format;
+use crate::owner::{self, Access};
+use crate::pattern::{self, Glob, RegexType};
+use crate::perm::Perm;
+use crate::predicate::{Cost, Predicate};
+use crate::sort::SortBy;
+use crate::stat::{self, Comparison, TimeField};
+use crate::EntryType;
+use clap::ValueEnum;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::Write;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+type MyResult<T> = Result<T, Box<dyn Error>>;
+
+/// A find expression, evaluated against each entry of the walk.
+#[derive(Debug)]
+pub enum Expr {
+    /// `! EXPR`, `-not EXPR`
+    Not(Box<Expr>),
+    /// `EXPR -a EXPR`, `EXPR EXPR`: the right side is skipped if the left is false
+    And(Box<Expr>, Box<Expr>),
+    /// `EXPR -o EXPR`: the right side is skipped if the left is true
+    Or(Box<Expr>, Box<Expr>),
+    /// `EXPR , EXPR`: both sides are evaluated, the value is the right one
+    List(Box<Expr>, Box<Expr>),
+    Predicate(Predicate),
+    Action(Action),
+}
+
+/// Options that apply to the whole walk rather than to each entry.
+#[derive(Debug, Default)]
+pub struct Options {
+    /// `-maxdepth`: do not descend below this depth
+    pub max_depth: Option<usize>,
+    /// `-mindepth`: do not evaluate the expression above this depth
+    pub min_depth: usize,
+    /// `-depth`: visit the contents of a directory before the directory itself
+    pub depth_first: bool,
+    /// `-P`, `-H`, `-L`: which symbolic links to follow
+    pub follow: Follow,
+    /// `-xdev`, `-mount`: do not descend into directories on other file systems
+    pub same_file_system: bool,
+    /// `-threads`: how many threads walk the tree; 0 or 1 walk it on this one
+    pub threads: usize,
+    /// `-sorted`: visit the entries of each directory in order of their names
+    pub sorted: bool,
+    /// `-sort`: print the results in this order once the walk is done
+    pub sort: Option<SortBy>,
+    /// `-respect-ignore`: skip what ignore files exclude, and hidden files
+    pub respect_ignore: bool,
+    /// `-hidden`: with `respect_ignore`, visit hidden files after all
+    pub hidden: bool,
+}
+
+/// A parsed command line.
+#[derive(Debug)]
+pub struct CommandLine {
+    pub paths: Vec<String>,
+    pub expr: Expr,
+    pub options: Options,
+    /// The files `-duplicates` collects, to be grouped after the walk
+    pub duplicates: Option<Arc<Duplicates>>,
+}
+
+impl From<Predicate> for Expr {
+    fn from(predicate: Predicate) -> Expr {
+        Expr::Predicate(predicate)
+    }
+}
+
+impl From<Action> for Expr {
+    fn from(action: Action) -> Expr {
+        Expr::Action(action)
+    }
+}
+
+impl Expr {
+    /// `self -a other`, for building an expression without parsing one.
+    pub fn and(self, other: impl Into<Expr>) -> Expr {
+        Expr::And(Box::new(self), Box::new(other.into()))
+    }
+
+    /// `self -o other`.
+    pub fn or(self, other: impl Into<Expr>) -> Expr {
+        Expr::Or(Box::new(self), Box::new(other.into()))
+    }
+
+    /// Evaluates the expression for `entry`.
+    pub fn eval<W: Write>(&self, entry: &Entry, ctx: &mut Context<W>) -> bool {
+        match self {
+            Expr::Not(expr) => !expr.eval(entry, ctx),
+            Expr::And(left, right) => left.eval(entry, ctx) && right.eval(entry, ctx),
+            Expr::Or(left, right) => left.eval(entry, ctx) || right.eval(entry, ctx),
+            Expr::List(left, right) => {
+                left.eval(entry, ctx);
+                right.eval(entry, ctx)
+            }
+            Expr::Predicate(predicate) => predicate.eval(entry),
+            Expr::Action(action) => action.eval(entry, ctx),
+        }
+    }
+
+    /// True if the expression contains an action that replaces the default `-print`.
+    fn has_output_action(&self) -> bool {
+        match self {
+            Expr::Not(expr) => expr.has_output_action(),
+            Expr::And(left, right) | Expr::Or(left, right) | Expr::List(left, right) => {
+                left.has_output_action() || right.has_output_action()
+            }
+            Expr::Predicate(_) => false,
+            Expr::Action(action) => !matches!(action, Action::Prune),
+        }
+    }
+
+    /// The cost of evaluating the expression, or None if it has actions,
+    /// whose place in it cannot change.
+    fn cost(&self) -> Option<Cost> {
+        match self {
+            Expr::Not(expr) => expr.cost(),
+            Expr::And(left, right) | Expr::Or(left, right) | Expr::List(left, right) => {
+                Some(left.cost()?.max(right.cost()?))
+            }
+            Expr::Predicate(predicate) => Some(predicate.cost()),
+            Expr::Action(_) => None,
+        }
+    }
+
+    /// Moves the cheaper side of `-a` and `-o` first where neither side has
+    /// actions, so that what reads files runs only for the entries that get
+    /// that far; the result is the same.
+    fn reorder(self) -> Expr {
+        match self {
+            Expr::Not(expr) => Expr::Not(Box::new(expr.reorder())),
+            Expr::And(left, right) => {
+                let (left, right) = Expr::cheaper_first(*left, *right);
+                Expr::And(Box::new(left), Box::new(right))
+            }
+            Expr::Or(left, right) => {
+                let (left, right) = Expr::cheaper_first(*left, *right);
+                Expr::Or(Box::new(left), Box::new(right))
+            }
+            Expr::List(left, right) => {
+                Expr::List(Box::new(left.reorder()), Box::new(right.reorder()))
+            }
+            expr => expr,
+        }
+    }
+
+    fn cheaper_first(left: Expr, right: Expr) -> (Expr, Expr) {
+        let (left, right) = (left.reorder(), right.reorder());
+        match (left.cost(), right.cost()) {
+            (Some(l), Some(r)) if l > r => (right, left),
+            _ => (left, right),
+        }
+    }
+
+    /// True if the expression runs commands or deletes files, which must
+    /// happen one at a time and in the order of the walk.
+    pub fn needs_sequential_walk(&self) -> bool {
+        match self {
+            Expr::Not(expr) => expr.needs_sequential_walk(),
+            Expr::And(left, right) | Expr::Or(left, right) | Expr::List(left, right) => {
+                left.needs_sequential_walk() || right.needs_sequential_walk()
+            }
+            Expr::Predicate(_) => false,
+            Expr::Action(action) => matches!(action, Action::Exec(_) | Action::Delete),
+        }
+    }
+}
+
+/// Splits the command line into options, starting points and an expression.
+///
+/// The options `-P`, `-H` and `-L` come first, the last one winning.
+/// Starting points are the arguments before the first one that looks like
+/// part of an expression, an option such as `-name` or one of `( ) ! ,`;
+/// they default to `.`. Without an action in the
+/// expression, `-print` is applied to every entry for which it is true.
+pub fn parse_command(args: &[String]) -> MyResult<CommandLine> {
+    let mut follow = Follow::Never;
+    let mut start = 0;
+    while let Some(arg) = args.get(start) {
+        follow = match arg.as_str() {
+            "-P" => Follow::Never,
+            "-H" => Follow::CommandLine,
+            "-L" => Follow::Always,
+            _ => break,
+        };
+        start += 1;
+    }
+    let args = &args[start..];
+
+    let split = args
+        .iter()
+        .position(|arg| {
+            (arg.starts_with('-') && arg.len() > 1) || ["(", ")", "!", ","].contains(&arg.as_str())
+        })
+        .unwrap_or(args.len());
+    let mut paths = args[..split].to_vec();
+    if paths.is_empty() {
+        paths.push(".".to_string());
+    }
+
+    let mut parser = ExprParser::new(&args[split..], follow);
+    let print = Expr::Action(Action::Print);
+    let expr = match parser.peek() {
+        None => print,
+        Some(_) => {
+            let expr = parser.parse_list()?.reorder();
+            if let Some(token) = parser.peek() {
+                return Err(match token {
+                    ")" => "invalid expression; you have too many ')'".into(),
+                    _ => format!("unexpected argument '{}'", token).into(),
+                });
+            }
+            if parser.has_delete && parser.has_prune && !parser.explicit_depth {
+                return Err(
+                    "The -delete action automatically turns on -depth, but -prune \
+                            does nothing when -depth is in effect. If you want to carry on \
+                            anyway, just explicitly use the -depth option."
+                        .into(),
+                );
+            }
+            // Removing what a followed link leads to would reach outside the tree.
+            if parser.has_delete && follow != Follow::Never {
+                return Err("-delete may not be used with -H or -L".into());
+            }
+            if expr.has_output_action() {
+                expr
+            } else {
+                Expr::And(Box::new(expr), Box::new(print))
+            }
+        }
+    };
+
+    Ok(CommandLine {
+        paths,
+        expr,
+        options: parser.options,
+        duplicates: parser.duplicates,
+    })
+}
+
+/// Recursive-descent parser for the expression, with GNU find's precedence
+/// from lowest to highest: `,`, `-o`, `-a` (or juxtaposition), `!`, `( )`.
+struct ExprParser {
+    tokens: Vec<String>,
+    pos: usize,
+    regex_type: RegexType,
+    options: Options,
+    /// The time the command started, which `-mtime` and friends measure from
+    now: SystemTime,
+    /// The value of the last `--option=value` token
+    inline_value: Option<String>,
+    /// How many `-exec` actions have been parsed, to number the next one
+    exec_count: usize,
+    /// Whether `-depth` itself was given, rather than implied by `-delete`
+    explicit_depth: bool,
+    has_prune: bool,
+    has_delete: bool,
+    duplicates: Option<Arc<Duplicates>>,
+}
+
+impl ExprParser {
+    fn new(args: &[String], follow: Follow) -> ExprParser {
+        ExprParser {
+            tokens: args.to_vec(),
+            pos: 0,
+            regex_type: RegexType::Emacs,
+            options: Options {
+                follow,
+                ..Options::default()
+            },
+            now: SystemTime::now(),
+            inline_value: None,
+            exec_count: 0,
+            explicit_depth: false,
+            has_prune: false,
+            has_delete: false,
+            duplicates: None,
+        }
+    }
+
+    /// The next operator or predicate, normalized.
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(|token| normalize(token))
+    }
+
+    /// Consumes the next operator or predicate, normalized. The value of
+    /// `--name=x` is kept for `expect_arg`.
+    fn next(&mut self) -> Option<&str> {
+        let token = self.tokens.get(self.pos);
+        self.pos += 1;
+        let token = token?;
+        self.inline_value = token
+            .strip_prefix("--")
+            .and_then(|option| option.split_once('='))
+            .map(|(_, value)| value.to_string());
+        Some(normalize(token))
+    }
+
+    /// Consumes the argument of `option` verbatim.
+    fn expect_arg(&mut self, option: &str) -> MyResult<String> {
+        if let Some(value) = self.inline_value.take() {
+            return Ok(value);
+        }
+        let arg = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        arg.ok_or_else(|| format!("missing argument to '{}'", option).into())
+    }
+
+    fn expect_number(&mut self, option: &str) -> MyResult<usize> {
+        let arg = self.expect_arg(option)?;
+        arg.parse().map_err(|_| {
+            format!(
+                "Expected a positive decimal integer argument to {}, but got '{}'",
+                option, arg
+            )
+            .into()
+        })
+    }
+
+    fn parse_list(&mut self) -> MyResult<Expr> {
+        let mut expr = self.parse_or()?;
+        while self.peek() == Some(",") {
+            self.next();
+            expr = Expr::List(Box::new(expr), Box::new(self.parse_or()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_or(&mut self) -> MyResult<Expr> {
+        let mut expr = self.parse_and()?;
+        while self.peek() == Some("-o") {
+            self.next();
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> MyResult<Expr> {
+        let mut expr = self.parse_not()?;
+        loop {
+            match self.peek() {
+                None | Some(")" | "-o" | ",") => return Ok(expr),
+                Some("-a") => {
+                    self.next();
+                }
+                Some(_) => {}
+            }
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_not()?));
+        }
+    }
+
+    fn parse_not(&mut self) -> MyResult<Expr> {
+        if self.peek() == Some("!") {
+            self.next();
+            return Ok(Expr::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> MyResult<Expr> {
+        let token = match self.next() {
+            Some(token) => token.to_string(),
+            None => return Err("invalid expression; expected an expression at the end".into()),
+        };
+
+        let expr = match token.as_str() {
+            "(" => {
+                if self.peek() == Some(")") {
+                    return Err("invalid expression; empty parentheses are not allowed".into());
+                }
+                let expr = self.parse_list()?;
+                if self.next() != Some(")") {
+                    return Err(
+                        "invalid expression; I was expecting to find a ')' somewhere \
+                                but did not see one"
+                            .into(),
+                    );
+                }
+                expr
+            }
+            "-a" | "-o" | "," | ")" => {
+                return Err(format!("invalid expression; '{}' without an argument", token).into())
+            }
+            "-true" => Expr::Predicate(Predicate::True),
+            "-false" => Expr::Predicate(Predicate::False),
+            "-name" | "-iname" => {
+                let pattern = self.expect_arg(&token)?;
+                Expr::Predicate(Predicate::Name(Glob::new(&pattern, token == "-iname")?))
+            }
+            "-path" | "-ipath" | "-wholename" | "-iwholename" => {
+                let pattern = self.expect_arg(&token)?;
+                let case_insensitive = token.starts_with("-i");
+                Expr::Predicate(Predicate::Path(Glob::new(&pattern, case_insensitive)?))
+            }
+            "-regex" | "-iregex" => {
+                let pattern = self.expect_arg(&token)?;
+                let re = pattern::new_regex(&pattern, self.regex_type, token == "-iregex")
+                    .map_err(|e| format!("invalid regular expression '{}': {}", pattern, e))?;
+                Expr::Predicate(Predicate::Regex(re))
+            }
+            "-regextype" => {
+                let value = self.expect_arg(&token)?;
+                self.regex_type = RegexType::from_str(&value, false)
+                    .map_err(|_| format!("Unknown regular expression type '{}'", value))?;
+                Expr::Predicate(Predicate::True)
+            }
+            "-type" | "-xtype" => {
+                let value = self.expect_arg(&token)?;
+                let types = parse_types(&value, &token)?;
+                Expr::Predicate(match token.as_str() {
+                    "-type" => Predicate::Type(types),
+                    _ => Predicate::XType(types),
+                })
+            }
+            "-size" => {
+                let arg = self.expect_arg(&token)?;
+                let (comparison, unit) = stat::parse_size(&arg)
+                    .ok_or_else(|| format!("invalid argument '{}' to -size", arg))?;
+                Expr::Predicate(Predicate::Size(comparison, unit))
+            }
+            "-empty" => Expr::Predicate(Predicate::Empty),
+            "-amin" | "-atime" | "-cmin" | "-ctime" | "-mmin" | "-mtime" => {
+                let arg = self.expect_arg(&token)?;
+                let comparison = Comparison::parse(&arg)
+                    .ok_or_else(|| format!("invalid argument '{}' to {}", arg, token))?;
+                let field = TimeField::from_letter(token.as_bytes()[1] as char).unwrap();
+                let unit = if token.ends_with("min") { 60 } else { 86400 };
+                Expr::Predicate(Predicate::Age {
+                    field,
+                    comparison,
+                    unit,
+                    now: self.now,
+                })
+            }
+            "-newer" | "-anewer" | "-cnewer" => {
+                let field = match token.as_str() {
+                    "-anewer" => TimeField::Access,
+                    "-cnewer" => TimeField::Change,
+                    _ => TimeField::Modify,
+                };
+                let reference = self.expect_arg(&token)?;
+                let time = reference_time(&reference, TimeField::Modify, self.options.follow)?;
+                Expr::Predicate(Predicate::Newer(field, time))
+            }
+            _ if token.starts_with("-newer") && token.len() == 8 => {
+                let (x, y) = (token.as_bytes()[6] as char, token.as_bytes()[7] as char);
+                let unknown = || format!("unknown predicate '{}'", token);
+                let field = TimeField::from_letter(x).ok_or_else(unknown)?;
+                let reference_field = match y {
+                    't' => None,
+                    _ => Some(TimeField::from_letter(y).ok_or_else(unknown)?),
+                };
+                let reference = self.expect_arg(&token)?;
+                let time = match reference_field {
+                    Some(reference_field) => {
+                        reference_time(&reference, reference_field, self.options.follow)?
+                    }
+                    None => stat::parse_time(&reference).ok_or_else(|| {
+                        format!(
+                            "I cannot figure out how to interpret '{}' as a date or time",
+                            reference
+                        )
+                    })?,
+                };
+                Expr::Predicate(Predicate::Newer(field, time))
+            }
+            "-uid" | "-gid" => {
+                let arg = self.expect_arg(&token)?;
+                let comparison = Comparison::parse(&arg)
+                    .ok_or_else(|| format!("invalid argument '{}' to {}", arg, token))?;
+                Expr::Predicate(match token.as_str() {
+                    "-uid" => Predicate::Uid(comparison),
+                    _ => Predicate::Gid(comparison),
+                })
+            }
+            "-user" => {
+                let name = self.expect_arg(&token)?;
+                let uid = owner::parse_user(&name)
+                    .ok_or_else(|| format!("'{}' is not the name of a known user", name))?;
+                Expr::Predicate(Predicate::Uid(Comparison::Exactly(uid.into())))
+            }
+            "-group" => {
+                let name = self.expect_arg(&token)?;
+                let gid = owner::parse_group(&name)
+                    .ok_or_else(|| format!("'{}' is not the name of an existing group", name))?;
+                Expr::Predicate(Predicate::Gid(Comparison::Exactly(gid.into())))
+            }
+            "-nouser" => Expr::Predicate(Predicate::NoUser),
+            "-nogroup" => Expr::Predicate(Predicate::NoGroup),
+            "-perm" => {
+                let arg = self.expect_arg(&token)?;
+                let perm = Perm::parse(&arg).ok_or_else(|| format!("invalid mode '{}'", arg))?;
+                Expr::Predicate(Predicate::Perm(perm))
+            }
+            "-readable" => Expr::Predicate(Predicate::Accessible(Access::Read)),
+            "-writable" => Expr::Predicate(Predicate::Accessible(Access::Write)),
+            "-executable" => Expr::Predicate(Predicate::Accessible(Access::Execute)),
+            "-inum" | "-links" => {
+                let arg = self.expect_arg(&token)?;
+                let comparison = Comparison::parse(&arg)
+                    .ok_or_else(|| format!("invalid argument '{}' to {}", arg, token))?;
+                Expr::Predicate(match token.as_str() {
+                    "-inum" => Predicate::Inum(comparison),
+                    _ => Predicate::Links(comparison),
+                })
+            }
+            "-contains" => {
+                let pattern = self.expect_arg(&token)?;
+                let re = regex::bytes::Regex::new(&pattern)
+                    .map_err(|e| format!("invalid regular expression '{}': {}", pattern, e))?;
+                Expr::Predicate(Predicate::Contains(re))
+            }
+            "-mime" => {
+                let pattern = self.expect_arg(&token)?;
+                Expr::Predicate(Predicate::Mime(Glob::new(&pattern, true)?))
+            }
+            "-samefile" => {
+                let reference = self.expect_arg(&token)?;
+                let metadata = match self.options.follow {
+                    Follow::Never => fs::symlink_metadata(&reference),
+                    _ => fs::metadata(&reference),
+                }
+                .map_err(|e| format!("{}: {}", reference, e))?;
+                let (dev, ino) =
+                    stat::inode(&metadata).ok_or("-samefile is not supported on this platform")?;
+                Expr::Predicate(Predicate::SameFile(dev, ino))
+            }
+            "-xdev" | "-mount" => {
+                self.options.same_file_system = true;
+                Expr::Predicate(Predicate::True)
+            }
+            "-maxdepth" => {
+                self.options.max_depth = Some(self.expect_number(&token)?);
+                Expr::Predicate(Predicate::True)
+            }
+            "-mindepth" => {
+                self.options.min_depth = self.expect_number(&token)?;
+                Expr::Predicate(Predicate::True)
+            }
+            "-threads" => {
+                match self.expect_number(&token)? {
+                    0 => return Err("invalid argument '0' to -threads".into()),
+                    threads => self.options.threads = threads,
+                }
+                Expr::Predicate(Predicate::True)
+            }
+            "-sorted" => {
+                self.options.sorted = true;
+                Expr::Predicate(Predicate::True)
+            }
+            "-sort" => {
+                let value = self.expect_arg(&token)?;
+                let sort = SortBy::from_str(&value, false)
+                    .map_err(|_| format!("invalid argument '{}' to -sort", value))?;
+                self.options.sort = Some(sort);
+                Expr::Predicate(Predicate::True)
+            }
+            "-respect-ignore" => {
+                self.options.respect_ignore = true;
+                Expr::Predicate(Predicate::True)
+            }
+            "-hidden" => {
+                self.options.hidden = true;
+                Expr::Predicate(Predicate::True)
+            }
+            "-depth" | "-d" => {
+                self.options.depth_first = true;
+                self.explicit_depth = true;
+                Expr::Predicate(Predicate::True)
+            }
+            "-print" => Expr::Action(Action::Print),
+            "-print0" => Expr::Action(Action::Print0),
+            "-ls" => Expr::Action(Action::Ls { now: self.now }),
+            "-json" => Expr::Action(Action::Json),
+            "-duplicates" => {
+                if self.duplicates.is_some() {
+                    return Err("-duplicates may only be given once".into());
+                }
+                let json = match self.inline_value.take().as_deref() {
+                    None | Some("text") => false,
+                    Some("json") => true,
+                    Some(other) => {
+                        return Err(format!("invalid argument '{}' to -duplicates", other).into())
+                    }
+                };
+                let duplicates = Arc::new(Duplicates::new(json));
+                self.duplicates = Some(Arc::clone(&duplicates));
+                Expr::Action(Action::Duplicates(duplicates))
+            }
+            "-printf" => {
+                let format = self.expect_arg(&token)?;
+                Expr::Action(Action::Printf(Format::parse(&format)?))
+            }
+            "-fprint" => {
+                let path = self.expect_arg(&token)?;
+                let file = File::create(&path).map_err(|e| format!("{}: {}", path, e))?;
+                Expr::Action(Action::FPrint(file))
+            }
+            "-prune" => {
+                self.has_prune = true;
+                Expr::Action(Action::Prune)
+            }
+            "-delete" => {
+                self.options.depth_first = true;
+                self.has_delete = true;
+                Expr::Action(Action::Delete)
+            }
+            "-exec" | "-execdir" | "-ok" | "-okdir" => {
+                Expr::Action(Action::Exec(self.parse_exec(&token)?))
+            }
+            _ if token.starts_with('-') => {
+                return Err(format!("unknown predicate '{}'", token).into())
+            }
+            _ => return Err(format!("paths must precede expression: '{}'", token).into()),
+        };
+
+        if self.inline_value.take().is_some() {
+            return Err(format!("option '-{}' doesn't allow an argument", token).into());
+        }
+        Ok(expr)
+    }
+
+    /// Reads the command of `-exec` and friends up to `;`, or up to `{} +`
+    /// for the batched form, which `-ok` does not have.
+    fn parse_exec(&mut self, option: &str) -> MyResult<Exec> {
+        let prompt = option.starts_with("-ok");
+        let mut command: Vec<String> = self.inline_value.take().into_iter().collect();
+        let batch = loop {
+            let word = match self.tokens.get(self.pos) {
+                Some(word) => word.clone(),
+                None => return Err(format!("missing argument to '{}'", option).into()),
+            };
+            self.pos += 1;
+            match word.as_str() {
+                ";" => break false,
+                "+" if !prompt && command.last().is_some_and(|last| last == "{}") => {
+                    command.pop();
+                    break true;
+                }
+                _ => command.push(word),
+            }
+        };
+
+        if command.is_empty() {
+            return Err(format!("invalid argument ';' to {}", option).into());
+        }
+        if batch && command.iter().any(|word| word.contains("{}")) {
+            return Err(format!(
+                "only one instance of {{}} is supported with {} ... +",
+                option
+            )
+            .into());
+        }
+
+        self.exec_count += 1;
+        Ok(Exec {
+            id: self.exec_count,
+            command,
+            batch,
+            in_dir: option.ends_with("dir"),
+            prompt,
+        })
+    }
+}
+
+/// Parses the comma-separated list of types of `-type` and `-xtype`.
+fn parse_types(value: &str, option: &str) -> MyResult<Vec<EntryType>> {
+    let mut types = Vec::new();
+    for name in value.split(',') {
+        if name.is_empty() {
+            return Err(format!(
+                "missing file type in the list argument to {}: '{}'",
+                option, value
+            )
+            .into());
+        }
+        let entry_type = EntryType::from_str(name, false)
+            .map_err(|_| format!("Unknown argument to {}: {}", option, name))?;
+        if types.contains(&entry_type) {
+            return Err(format!(
+                "Duplicate file type '{}' in the argument list to {}",
+                name, option
+            )
+            .into());
+        }
+        types.push(entry_type);
+    }
+    Ok(types)
+}
+
+/// Reads timestamp `field` of the file `reference` for `-newer` and friends,
+/// or of what it links to under `-H` and `-L`.
+fn reference_time(reference: &str, field: TimeField, follow: Follow) -> MyResult<SystemTime> {
+    let metadata = match follow {
+        Follow::Never => fs::symlink_metadata(reference),
+        _ => fs::metadata(reference),
+    }
+    .map_err(|e| format!("{}: {}", reference, e))?;
+    field.of(&metadata).ok_or_else(|| {
+        format!(
+            "{}: this platform does not record the requested timestamp",
+            reference
+        )
+        .into()
+    })
+}
+
+/// Maps the accepted spellings of operators and predicates to a single one:
+/// long options may be written with two dashes (`--name`), `-n` and `-t`
+/// abbreviate `-name` and `-type`, and the word operators become symbols.
+/// The value of `--name=x` is dropped.
+fn normalize(arg: &str) -> &str {
+    let arg = match arg.strip_prefix('-') {
+        Some(rest) if rest.starts_with('-') && rest.len() > 1 => {
+            rest.split_once('=').map_or(rest, |(name, _)| name)
+        }
+        _ => arg,
+    };
+    match arg {
+        "-n" => "-name",
+        "-t" => "-type",
+        "-not" => "!",
+        "-and" => "-a",
+        "-or" => "-o",
+        _ => arg,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_command, Action, Expr, Predicate};
+
+    fn parse(args: &[&str]) -> Expr {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        parse_command(&args).unwrap().expr
+    }
+
+    #[test]
+    fn test_reorder_cheaper_first() {
+        let expr = parse(&["-contains", "x", "-size", "-1k", "-o", "-name", "y"]);
+        let Expr::And(or, _print) = expr else {
+            panic!("{:?}", expr);
+        };
+        let Expr::Or(left, right) = *or else {
+            panic!("{:?}", or);
+        };
+        assert!(matches!(*left, Expr::Predicate(Predicate::Name(_))));
+        let Expr::And(size, contains) = *right else {
+            panic!("{:?}", right);
+        };
+        assert!(matches!(*size, Expr::Predicate(Predicate::Size(..))));
+        assert!(matches!(*contains, Expr::Predicate(Predicate::Contains(_))));
+    }
+
+    #[test]
+    fn test_reorder_keeps_actions() {
+        let expr = parse(&["-contains", "x", "-print", "-name", "y"]);
+        let Expr::And(left, right) = expr else {
+            panic!("{:?}", expr);
+        };
+        assert!(matches!(*right, Expr::Predicate(Predicate::Name(_))));
+        assert!(
+            matches!(*left, Expr::And(_, ref print) if matches!(**print, Expr::Action(Action::Print)))
+        );
+    }
+}
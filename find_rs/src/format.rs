@@ -0,0 +1,535 @@
+use crate::entry::Entry;
+use crate::owner;
+use crate::perm;
+use crate::stat::{self, TimeField};
+use crate::EntryType;
+use chrono::{DateTime, Local, SecondsFormat, Utc};
+use clap::ValueEnum;
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A parsed `-printf` format.
+#[derive(Debug, Clone)]
+pub struct Format {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone)]
+enum Part {
+    Literal(Vec<u8>),
+    Directive {
+        /// `-`: pad on the right rather than the left
+        left: bool,
+        width: usize,
+        /// At most this many characters of the value
+        precision: Option<usize>,
+        kind: Directive,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Directive {
+    /// `%p`
+    Path,
+    /// `%f`
+    Name,
+    /// `%h`
+    Dir,
+    /// `%P`
+    RelativePath,
+    /// `%s`
+    Size,
+    /// `%m`
+    Mode,
+    /// `%M`
+    SymbolicMode,
+    /// `%u`
+    User,
+    /// `%g`
+    Group,
+    /// `%a`, `%c`, `%t`: the timestamp in the format of ctime(3)
+    Time(TimeField),
+    /// `%Ak`, `%Ck`, `%Tk`: part `k` of the timestamp, `@` for seconds since the epoch
+    TimePart(TimeField, char),
+    /// `%y`
+    Type,
+    /// `%d`
+    Depth,
+}
+
+impl Format {
+    /// Parses a format with the backslash escapes of GNU find. Unknown
+    /// directives are printed as they are, after a warning.
+    pub fn parse(format: &str) -> Result<Format, String> {
+        let mut parts = Vec::new();
+        let mut literal = Vec::new();
+        let mut chars = format.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some('a') => literal.push(b'\x07'),
+                    Some('b') => literal.push(b'\x08'),
+                    Some('c') => break,
+                    Some('f') => literal.push(b'\x0c'),
+                    Some('n') => literal.push(b'\n'),
+                    Some('r') => literal.push(b'\r'),
+                    Some('t') => literal.push(b'\t'),
+                    Some('v') => literal.push(b'\x0b'),
+                    Some('\\') => literal.push(b'\\'),
+                    Some(d @ '0'..='7') => {
+                        let mut code = d.to_digit(8).unwrap();
+                        for _ in 0..2 {
+                            match chars.peek().and_then(|c| c.to_digit(8)) {
+                                Some(digit) => {
+                                    code = code * 8 + digit;
+                                    chars.next();
+                                }
+                                None => break,
+                            }
+                        }
+                        // \NNN is a byte, which need not be UTF-8 on its own.
+                        literal.push(code as u8);
+                    }
+                    Some(other) => {
+                        crate::report(format_args!("warning: unrecognized escape '\\{}'", other));
+                        literal.push(b'\\');
+                        push_char(&mut literal, other);
+                    }
+                    None => literal.push(b'\\'),
+                },
+                '%' => {
+                    let mut spec = String::from("%");
+                    let left = chars.next_if_eq(&'-').is_some();
+                    if left {
+                        spec.push('-');
+                    }
+                    let width = take_number(&mut chars, &mut spec).unwrap_or(0);
+                    let precision = chars.next_if_eq(&'.').map(|_| {
+                        spec.push('.');
+                        take_number(&mut chars, &mut spec).unwrap_or(0)
+                    });
+                    let letter = chars.next().ok_or("% at end of format string")?;
+                    spec.push(letter);
+                    let kind = match letter {
+                        '%' => {
+                            literal.push(b'%');
+                            continue;
+                        }
+                        'p' => Directive::Path,
+                        'f' => Directive::Name,
+                        'h' => Directive::Dir,
+                        'P' => Directive::RelativePath,
+                        's' => Directive::Size,
+                        'm' => Directive::Mode,
+                        'M' => Directive::SymbolicMode,
+                        'u' => Directive::User,
+                        'g' => Directive::Group,
+                        'a' => Directive::Time(TimeField::Access),
+                        'c' => Directive::Time(TimeField::Change),
+                        't' => Directive::Time(TimeField::Modify),
+                        'y' => Directive::Type,
+                        'd' => Directive::Depth,
+                        'A' | 'C' | 'T' => {
+                            let field = match letter {
+                                'A' => TimeField::Access,
+                                'C' => TimeField::Change,
+                                _ => TimeField::Modify,
+                            };
+                            match chars.next() {
+                                Some(part)
+                                    if part == '@' || part.is_ascii_alphabetic() || part == '+' =>
+                                {
+                                    Directive::TimePart(field, part)
+                                }
+                                Some(part) => {
+                                    spec.push(part);
+                                    crate::report(format_args!(
+                                        "warning: unrecognized format directive '{}'",
+                                        spec
+                                    ));
+                                    literal.extend_from_slice(spec.as_bytes());
+                                    continue;
+                                }
+                                None => return Err(format!("missing time field after {}", spec)),
+                            }
+                        }
+                        _ => {
+                            crate::report(format_args!(
+                                "warning: unrecognized format directive '{}'",
+                                spec
+                            ));
+                            literal.extend_from_slice(spec.as_bytes());
+                            continue;
+                        }
+                    };
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Directive {
+                        left,
+                        width,
+                        precision,
+                        kind,
+                    });
+                }
+                _ => push_char(&mut literal, c),
+            }
+        }
+
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(Format { parts })
+    }
+
+    /// Formats `entry`. Paths are written as the bytes the platform has,
+    /// and widths and precisions count bytes, as printf(3) does.
+    pub fn render(&self, entry: &Entry) -> Vec<u8> {
+        let mut out = Vec::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(bytes) => out.extend_from_slice(bytes),
+                Part::Directive {
+                    left,
+                    width,
+                    precision,
+                    kind,
+                } => {
+                    let mut value = kind.render(entry);
+                    if let Some(precision) = precision {
+                        value.truncate(*precision);
+                    }
+                    let padding = width.saturating_sub(value.len());
+                    if !left {
+                        out.resize(out.len() + padding, b' ');
+                    }
+                    out.extend_from_slice(&value);
+                    if *left {
+                        out.resize(out.len() + padding, b' ');
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+fn push_char(bytes: &mut Vec<u8>, c: char) {
+    bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+}
+
+fn take_number(
+    chars: &mut std::iter::Peekable<std::str::Chars>,
+    spec: &mut String,
+) -> Option<usize> {
+    let mut digits = String::new();
+    while let Some(digit) = chars.next_if(|c| c.is_ascii_digit()) {
+        digits.push(digit);
+    }
+    spec.push_str(&digits);
+    digits.parse().ok()
+}
+
+impl Directive {
+    /// The value of the directive; paths are the bytes the platform has.
+    fn render(self, entry: &Entry) -> Vec<u8> {
+        let path = entry.path();
+        let metadata = entry.metadata().ok();
+        let owner = metadata.as_ref().and_then(stat::owner);
+        let text = match self {
+            Directive::Path => return path_bytes(path).into_owned(),
+            Directive::Name => return split_path(path).1,
+            Directive::Dir => return split_path(path).0,
+            Directive::RelativePath => {
+                return path_bytes(&relative_path(path, entry.depth())).into_owned()
+            }
+            Directive::Size => metadata.map_or(0, |m| m.len()).to_string(),
+            Directive::Mode => format!("{:o}", metadata.map_or(0, |m| stat::mode(&m))),
+            Directive::SymbolicMode => perm::symbolic_mode(
+                metadata.map_or(0, |m| stat::mode(&m)),
+                stat::type_char(entry.file_type()),
+            ),
+            Directive::User => match owner {
+                Some((uid, _)) => owner::user_name(uid).unwrap_or_else(|| uid.to_string()),
+                None => String::new(),
+            },
+            Directive::Group => match owner {
+                Some((_, gid)) => owner::group_name(gid).unwrap_or_else(|| gid.to_string()),
+                None => String::new(),
+            },
+            Directive::Time(field) => match metadata.and_then(|m| field.of(&m)) {
+                Some(time) => {
+                    let local = DateTime::<Local>::from(time);
+                    format!(
+                        "{}.{:09}0 {}",
+                        local.format("%a %b %e %H:%M:%S"),
+                        nanos(time),
+                        local.format("%Y")
+                    )
+                }
+                None => String::new(),
+            },
+            Directive::TimePart(field, part) => match metadata.and_then(|m| field.of(&m)) {
+                Some(time) => time_part(time, part),
+                None => String::new(),
+            },
+            Directive::Type => stat::type_char(entry.file_type()).to_string(),
+            Directive::Depth => entry.depth().to_string(),
+        };
+        text.into_bytes()
+    }
+}
+
+/// A line of `-ls`, in the columns of `ls -dils`: inode, 1K blocks, mode,
+/// links, owner, group, size (or device numbers), modification time and
+/// path, followed by ` -> TARGET` for a link. The time shows the year
+/// rather than the time of day unless it is within six months before `now`.
+pub fn render_ls(entry: &Entry, now: SystemTime) -> String {
+    let Ok(metadata) = entry.metadata() else {
+        return format!("{}\n", entry.path().display());
+    };
+    let (uid, gid) = stat::owner(&metadata).unwrap_or_default();
+    let size = match stat::device(&metadata) {
+        Some((major, minor)) => format!("{:>3}, {:>3}", major, minor),
+        None => metadata.len().to_string(),
+    };
+    let time = match metadata.modified() {
+        Ok(time) => {
+            let recent = now
+                .checked_sub(Duration::from_secs(SIX_MONTHS))
+                .is_some_and(|start| start <= time)
+                && time <= now;
+            let local = DateTime::<Local>::from(time);
+            local
+                .format(match recent {
+                    true => "%b %e %H:%M",
+                    false => "%b %e  %Y",
+                })
+                .to_string()
+        }
+        Err(_) => String::new(),
+    };
+    let mut line = format!(
+        "{:>9} {:>6} {} {:>3} {:<8} {:<8} {:>8} {} {}",
+        stat::inode(&metadata).map_or(0, |(_, inode)| inode),
+        stat::blocks(&metadata),
+        perm::symbolic_mode(stat::mode(&metadata), stat::type_char(metadata.file_type())),
+        stat::links(&metadata).unwrap_or(1),
+        owner::user_name(uid).unwrap_or_else(|| uid.to_string()),
+        owner::group_name(gid).unwrap_or_else(|| gid.to_string()),
+        size,
+        time,
+        entry.path().display()
+    );
+    if metadata.file_type().is_symlink() {
+        if let Ok(target) = fs::read_link(entry.path()) {
+            let _ = write!(line, " -> {}", target.display());
+        }
+    }
+    line.push('\n');
+    line
+}
+
+/// Half of the average Gregorian year, as ls(1) measures six months.
+const SIX_MONTHS: u64 = 31_556_952 / 2;
+
+/// A line of `-json`: an object with the path, type (as `-type` names it),
+/// size, octal mode, owner and group ids, modification and status change
+/// times in RFC 3339, inode, depth and, for a link, its target. What cannot
+/// be read is null. A path or target that is not UTF-8 is also given as its
+/// raw bytes, in `path_bytes` or `target_bytes`.
+pub fn render_json(entry: &Entry) -> String {
+    let metadata = entry.metadata().ok();
+    let number = |value: Option<u64>| value.map_or("null".to_string(), |n| n.to_string());
+    let time = |field: TimeField| match metadata.as_ref().and_then(|m| field.of(m)) {
+        Some(time) => {
+            json_string(&DateTime::<Utc>::from(time).to_rfc3339_opts(SecondsFormat::AutoSi, true))
+        }
+        None => "null".to_string(),
+    };
+    let type_char = stat::type_char(entry.file_type());
+    let entry_type = EntryType::value_variants()
+        .iter()
+        .find(|entry_type| entry_type.letter() == type_char)
+        .and_then(|entry_type| entry_type.to_possible_value())
+        .map_or("unknown".to_string(), |value| value.get_name().to_string());
+    let owner = metadata.as_ref().and_then(stat::owner);
+    let target = match entry.file_type().is_symlink() {
+        true => fs::read_link(entry.path())
+            .ok()
+            .map(|target| json_name("target", &target)),
+        false => None,
+    };
+    format!(
+        "{{{},\"type\":{},\"size\":{},\"mode\":{},\"uid\":{},\"gid\":{},\
+         \"mtime\":{},\"ctime\":{},\"inode\":{},\"depth\":{},{}}}\n",
+        json_name("path", entry.path()),
+        json_string(&entry_type),
+        number(metadata.as_ref().map(|m| m.len())),
+        metadata.as_ref().map_or("null".to_string(), |m| {
+            json_string(&format!("{:04o}", stat::mode(m)))
+        }),
+        number(owner.map(|(uid, _)| uid.into())),
+        number(owner.map(|(_, gid)| gid.into())),
+        time(TimeField::Modify),
+        time(TimeField::Change),
+        number(
+            metadata
+                .as_ref()
+                .and_then(stat::inode)
+                .map(|(_, inode)| inode)
+        ),
+        entry.depth(),
+        target.unwrap_or("\"target\":null".to_string()),
+    )
+}
+
+/// The member `key` with `path` as a JSON string, followed by `KEY_bytes`
+/// with its raw bytes when it is not UTF-8, since the string then stands in
+/// U+FFFD for what could not be decoded.
+fn json_name(key: &str, path: &Path) -> String {
+    let mut out = format!("\"{}\":{}", key, json_string(&path.to_string_lossy()));
+    if path.to_str().is_none() {
+        let bytes: Vec<String> = path_bytes(path).iter().map(u8::to_string).collect();
+        let _ = write!(out, ",\"{}_bytes\":[{}]", key, bytes.join(","));
+    }
+    out
+}
+
+/// `text` as a JSON string, quoted and escaped.
+pub fn json_string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Splits a path into its leading directories and last component, as `%h`
+/// and `%f` print them: `.` stands for the directory of a bare name, and
+/// the root is its own name.
+fn split_path(path: &Path) -> (Vec<u8>, Vec<u8>) {
+    let bytes = path_bytes(path);
+    let end = bytes.iter().rposition(|&b| b != b'/').map_or(0, |i| i + 1);
+    let trimmed = &bytes[..end];
+    if trimmed.is_empty() && !bytes.is_empty() {
+        return (Vec::new(), b"/".to_vec());
+    }
+    match trimmed.iter().rposition(|&b| b == b'/') {
+        Some(slash) => (trimmed[..slash].to_vec(), trimmed[slash + 1..].to_vec()),
+        None => (b".".to_vec(), trimmed.to_vec()),
+    }
+}
+
+/// The bytes of a path as the platform has them, where it allows.
+pub fn path_bytes(path: &Path) -> Cow<'_, [u8]> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        Cow::Borrowed(path.as_os_str().as_bytes())
+    }
+    #[cfg(not(unix))]
+    match path.to_string_lossy() {
+        Cow::Borrowed(text) => Cow::Borrowed(text.as_bytes()),
+        Cow::Owned(text) => Cow::Owned(text.into_bytes()),
+    }
+}
+
+/// The last `depth` components of `path`: the path below its starting point.
+fn relative_path(path: &Path, depth: usize) -> PathBuf {
+    let components: Vec<Component> = path.components().collect();
+    components[components.len().saturating_sub(depth)..]
+        .iter()
+        .collect()
+}
+
+fn nanos(time: SystemTime) -> u32 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(since) => since.subsec_nanos(),
+        Err(_) => 0,
+    }
+}
+
+/// Part `part` of a timestamp: `@` for seconds since the epoch, `+` for
+/// date and time, or a conversion of strftime(3).
+fn time_part(time: SystemTime, part: char) -> String {
+    let local = DateTime::<Local>::from(time);
+    let fraction = format!(".{:09}0", nanos(time));
+    match part {
+        '@' => {
+            let secs = match time.duration_since(UNIX_EPOCH) {
+                Ok(since) => since.as_secs() as i64,
+                Err(before) => -(before.duration().as_secs() as i64),
+            };
+            format!("{}{}", secs, fraction)
+        }
+        '+' => format!("{}{}", local.format("%Y-%m-%d+%H:%M:%S"), fraction),
+        'S' | 'T' => format!("{}{}", local.format(&format!("%{}", part)), fraction),
+        _ => {
+            let mut out = String::new();
+            match write!(out, "{}", local.format(&format!("%{}", part))) {
+                Ok(()) => out,
+                Err(_) => String::new(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{json_string, relative_path, split_path, time_part, Format, Part};
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_parse_format() {
+        assert!(Format::parse("%").is_err());
+        let format = Format::parse("%%\\t%-5s|%.2p\\101\\c ignored").unwrap();
+        assert_eq!(format.parts.len(), 5);
+        let format = Format::parse("\\377\u{e9}").unwrap();
+        assert!(matches!(&format.parts[..], [Part::Literal(bytes)] if bytes == b"\xff\xc3\xa9"));
+    }
+
+    #[test]
+    fn test_split_path() {
+        let split = |path| split_path(Path::new(path));
+        assert_eq!(split("a/b/c"), ("a/b".into(), "c".into()));
+        assert_eq!(split("c"), (".".into(), "c".into()));
+        assert_eq!(split("/"), ("".into(), "/".into()));
+        assert_eq!(split("/c"), ("".into(), "c".into()));
+    }
+
+    #[test]
+    fn test_relative_path() {
+        assert_eq!(relative_path(Path::new("a/b/c"), 2), PathBuf::from("b/c"));
+        assert_eq!(relative_path(Path::new("a/b/c"), 0), PathBuf::new());
+    }
+
+    #[test]
+    fn test_json_string() {
+        assert_eq!(json_string("a b"), r#""a b""#);
+        assert_eq!(json_string("q\"s\\n\n\x01"), r#""q\"s\\n\n\u0001""#);
+    }
+
+    #[test]
+    fn test_epoch_seconds() {
+        let time = UNIX_EPOCH + Duration::new(1727421796, 5);
+        assert_eq!(time_part(time, '@'), "1727421796.0000000050");
+    }
+}
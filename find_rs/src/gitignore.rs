@@ -0,0 +1,167 @@
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// `-respect-ignore`: skips what `.ignore` and git's ignore files exclude,
+/// `.git` directories, and hidden files unless `-hidden`, as fd(1) does.
+#[derive(Debug)]
+pub struct IgnoreFilter {
+    hidden: bool,
+    /// `core.excludesFile`, or `$XDG_CONFIG_HOME/git/ignore`
+    global: Gitignore,
+    /// The rules of each directory, by its path in the walk and its canonical
+    /// path, loaded as the walk reaches it
+    dirs: Mutex<HashMap<PathBuf, Arc<Rules>>>,
+}
+
+/// The ignore files that apply in a directory.
+#[derive(Debug)]
+struct Rules {
+    /// The canonical path of the directory
+    dir: PathBuf,
+    /// Its `.ignore`, then `.gitignore`, then `.git/info/exclude`; the first
+    /// of them with a match decides
+    matchers: Vec<Gitignore>,
+    /// The root of the git repository the directory is in
+    repo: Option<PathBuf>,
+    parent: Option<Arc<Rules>>,
+}
+
+impl IgnoreFilter {
+    pub fn new(hidden: bool) -> IgnoreFilter {
+        let (global, error) = Gitignore::global();
+        if let Some(e) = error {
+            crate::report(format_args!("warning: {}", e));
+        }
+        IgnoreFilter {
+            hidden,
+            global,
+            dirs: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether the entry at `path`, `depth` levels below its starting point,
+    /// is left out of the walk. Starting points never are.
+    pub fn is_ignored(&self, path: &Path, depth: usize, is_dir: bool) -> bool {
+        let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+            return false;
+        };
+        if depth == 0 {
+            return false;
+        }
+        // The repository itself is never of interest, even with -hidden.
+        if name == ".git" || !self.hidden && name.as_encoded_bytes().starts_with(b".") {
+            return true;
+        }
+        let Some(rules) = self.rules(parent) else {
+            return false;
+        };
+        let path = rules.dir.join(name);
+        let mut level = Some(&rules);
+        while let Some(rules) = level {
+            for matcher in &rules.matchers {
+                match matcher.matched(&path, is_dir) {
+                    Match::None => {}
+                    Match::Ignore(_) => return true,
+                    Match::Whitelist(_) => return false,
+                }
+            }
+            level = rules.parent.as_ref();
+        }
+        match &rules.repo {
+            Some(repo) => {
+                let path = path.strip_prefix(repo).unwrap_or(&path);
+                self.global.matched(path, is_dir).is_ignore()
+            }
+            None => false,
+        }
+    }
+
+    /// Whether an entry or one of the directories between it and its
+    /// starting point is ignored, for a walk that visits the contents of a
+    /// directory before the directory itself.
+    pub fn is_ignored_below(&self, path: &Path, depth: usize, is_dir: bool) -> bool {
+        self.is_ignored(path, depth, is_dir)
+            || path
+                .ancestors()
+                .skip(1)
+                .zip((1..depth).rev())
+                .any(|(dir, depth)| self.is_ignored(dir, depth, true))
+    }
+
+    /// The rules of the directory at `dir`, loading those of the
+    /// directories above it as well.
+    fn rules(&self, dir: &Path) -> Option<Arc<Rules>> {
+        if let Some(rules) = self.dirs.lock().unwrap().get(dir) {
+            return Some(Arc::clone(rules));
+        }
+        let canonical = fs::canonicalize(if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        })
+        .ok()?;
+        let cached = self.dirs.lock().unwrap().get(&canonical).cloned();
+        let rules = match cached {
+            Some(rules) => rules,
+            None => {
+                let parent = canonical.parent().and_then(|parent| self.rules(parent));
+                let rules = Arc::new(Rules::load(canonical.clone(), parent));
+                self.dirs
+                    .lock()
+                    .unwrap()
+                    .insert(canonical, Arc::clone(&rules));
+                rules
+            }
+        };
+        self.dirs
+            .lock()
+            .unwrap()
+            .insert(dir.to_path_buf(), Arc::clone(&rules));
+        Some(rules)
+    }
+}
+
+impl Rules {
+    fn load(dir: PathBuf, parent: Option<Arc<Rules>>) -> Rules {
+        let git = dir.join(".git");
+        let repo = match git.exists() {
+            true => Some(dir.clone()),
+            false => parent.as_ref().and_then(|parent| parent.repo.clone()),
+        };
+        let mut files = vec![dir.join(".ignore")];
+        if repo.is_some() {
+            files.push(dir.join(".gitignore"));
+        }
+        if git.is_dir() {
+            files.push(git.join("info").join("exclude"));
+        }
+
+        let matchers = files
+            .into_iter()
+            .filter(|file| file.is_file())
+            .filter_map(|file| {
+                let mut builder = GitignoreBuilder::new(&dir);
+                if let Some(e) = builder.add(&file) {
+                    crate::report(format_args!("warning: {}", e));
+                }
+                match builder.build() {
+                    Ok(matcher) => Some(matcher),
+                    Err(e) => {
+                        crate::report(format_args!("warning: {}", e));
+                        None
+                    }
+                }
+            })
+            .collect();
+        Rules {
+            dir,
+            matchers,
+            repo,
+            parent,
+        }
+    }
+}
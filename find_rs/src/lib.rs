@@ -1,73 +1,412 @@
-// use crate::EntryType::*;
+//! find(1) in Rust. Besides the command, expressions may be built from a
+//! [`Predicate`] and [`Action`] at a time and evaluated against an [`Entry`]:
+//!
+//! ```
+//! use find_rs::{Action, Comparison, Context, Entry, EntryType, Expr, Glob, Predicate};
+//!
+//! let expr = Expr::from(Predicate::Type(vec![EntryType::File]))
+//!     .and(Predicate::Name(Glob::new("*.toml", false).unwrap()))
+//!     .and(Predicate::Size(Comparison::Greater(0), 1))
+//!     .and(Action::Print);
+//! let entry = Entry::new("Cargo.toml".into(), 0, false).unwrap();
+//! let mut ctx = Context::new(Vec::new());
+//! assert!(expr.eval(&entry, &mut ctx));
+//! assert_eq!(ctx.output, b"Cargo.toml\n");
+//! ```
+
+mod action;
+mod content;
+mod duplicates;
+mod entry;
+mod exec;
+mod expr;
+mod format;
+mod gitignore;
+mod owner;
+mod parallel;
+mod pattern;
+mod perm;
+mod predicate;
+mod sort;
+mod stat;
+#[cfg(test)]
+mod test_fixture;
+
+pub use action::{Action, Context};
 use clap::Parser;
 use clap::ValueEnum;
-use regex::Regex;
+pub use duplicates::{DuplicateSet, Duplicates};
+pub use entry::{Entry, Follow};
+pub use exec::Exec;
+pub use expr::{parse_command, CommandLine, Expr, Options};
+pub use format::Format;
+pub use gitignore::IgnoreFilter;
+pub use owner::Access;
+pub use pattern::{Glob, RegexType};
+pub use perm::{Perm, PermMatch};
+pub use predicate::Predicate;
+pub use sort::{SortBy, SortKey};
+pub use stat::{Comparison, TimeField};
 use std::error::Error;
-use walkdir::DirEntry;
+use std::fmt::Display;
+use std::fs;
+use std::io::{self, BufWriter, ErrorKind, Write};
+use std::path::Path;
 use walkdir::WalkDir;
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 
-#[derive(Debug, Clone, ValueEnum, PartialEq)]
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq)]
 pub enum EntryType {
+    #[value(alias = "d")]
     Dir,
+    #[value(alias = "f")]
     File,
+    #[value(alias = "l")]
     Link,
+    #[value(alias = "b")]
+    BlockDevice,
+    #[value(alias = "c")]
+    CharDevice,
+    #[value(alias = "p")]
+    Fifo,
+    #[value(alias = "s")]
+    Socket,
+}
+
+impl EntryType {
+    /// The letter of the type, as `%y` prints it.
+    pub fn letter(self) -> char {
+        match self {
+            EntryType::Dir => 'd',
+            EntryType::File => 'f',
+            EntryType::Link => 'l',
+            EntryType::BlockDevice => 'b',
+            EntryType::CharDevice => 'c',
+            EntryType::Fifo => 'p',
+            EntryType::Socket => 's',
+        }
+    }
 }
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
+#[command(after_help = EXPRESSION_HELP)]
 pub struct Args {
-    /// target file path
-    #[arg(default_value = ".")]
-    paths: Vec<String>,
-
-    /// True if the last component of the pathname being examined matches pattern
-    #[arg(short = 'n', long = "name")]
-    names: Vec<Regex>,
-
-    /// True if the file is of the specified type
-    #[arg(short, long)]
-    #[clap(value_enum)]
-    r#type: Vec<EntryType>,
+    /// -P, -H or -L, then starting points (default: .) followed by an expression
+    #[arg(
+        value_name = "[-P|-H|-L] PATH... EXPRESSION",
+        allow_hyphen_values = true,
+        trailing_var_arg = true
+    )]
+    args: Vec<String>,
+}
+
+const EXPRESSION_HELP: &str = "\
+Symbolic links:
+    -P                  never follow symbolic links (the default)
+    -H                  follow symbolic links given as starting points
+    -L                  follow all symbolic links; -type l then matches only broken links
+
+Expression:
+  Operators, from highest to lowest precedence:
+    ( EXPR )            group
+    ! EXPR, -not EXPR   true if EXPR is false
+    EXPR -a EXPR        and (also EXPR EXPR, -and); the right side runs only if the left is true
+    EXPR -o EXPR        or (also -or); the right side runs only if the left is false
+    EXPR , EXPR         evaluate both, the value is the right side
+
+  Tests:
+    -name PATTERN       last path component matches the shell pattern (-n); -iname ignores case
+    -path PATTERN       whole path matches the shell pattern; -ipath ignores case
+    -regex REGEX        whole path matches the regular expression; -iregex ignores case
+    -regextype TYPE     syntax of later -regex: emacs, posix-basic, posix-extended, rust
+    -type TYPE[,TYPE]   file is of one of the types (-t): f regular file, d directory,
+                        l symbolic link, b block device, c character device, p FIFO,
+                        s socket
+    -xtype TYPE         like -type, but for a symbolic link the type of its target;
+                        with -L, that of the link itself
+    -size [+-]N[bcwkMG] size rounded up to units of N (default 512-byte blocks)
+    -empty              empty regular file or directory
+    -mtime [+-]N        modified N days ago, counting whole days; -atime, -ctime
+    -mmin [+-]N         modified N minutes ago, rounded up only for exact N; -amin, -cmin
+    -newer FILE         modified more recently than FILE; -anewer, -cnewer
+    -newerXY REF        timestamp X of the file is newer than timestamp Y of REF,
+                        where X and Y are a, B, c or m; Y may be t for a date REF
+    -user NAME          owned by the user NAME or numeric id; -group NAME
+    -uid [+-]N          owned by user id N; -gid [+-]N
+    -nouser             owned by a user id with no entry in the password database; -nogroup
+    -perm MODE          permission bits are exactly MODE, octal or symbolic (u+w,g=r);
+                        -perm -MODE: all of MODE's bits are set; /MODE: any of them is
+    -readable           the current user may read the file; -writable, -executable
+    -inum [+-]N         inode number is N
+    -samefile FILE      same file as FILE: a hard link to it, or FILE itself
+    -links [+-]N        file has N hard links
+    -contains REGEX     a line of the regular file matches REGEX, in the syntax of -regextype
+                        rust and not anchored; only the first MiB of a longer line is tested
+    -mime TYPE          the media type from the first bytes of the file matches the shell
+                        pattern TYPE (text/*, image/png); other than regular files are
+                        inode/directory, inode/symlink... and empty files inode/x-empty
+    -true, -false       always true, always false
+
+  Options (true, and apply to the whole walk wherever they appear):
+    -maxdepth N         descend at most N levels below the starting points
+    -mindepth N         do not apply tests or actions above level N
+    -depth              process a directory's contents before the directory itself
+    -xdev, -mount       do not descend into directories on other file systems
+    -threads N          walk with N threads, printing entries in the order they are found;
+                        one thread is used with -exec, -ok, -delete or -depth
+    -respect-ignore     skip what .gitignore, .git/info/exclude, git's global excludes and
+                        .ignore files exclude, as git applies them, and hidden files
+    -hidden             with -respect-ignore, do not skip hidden files other than .git
+    -sort KEY           print the results once the walk is done, ordered by KEY: name
+                        (by path), size (largest first) or mtime (newest first)
+    -sorted             visit the entries of each directory in order of their names;
+                        with -threads, the output is printed once the walk is done
+
+  Actions:
+    -print              print the path; the default when the expression has no action
+    -print0             print the path followed by a NUL character instead of a newline
+    -ls                 print a line like ls -dils: inode, blocks, mode, links, owner, group,
+                        size, modification time and path
+    -json               print a JSON object per line with the path, type, size, mode, uid,
+                        gid, mtime, ctime, inode, depth and link target of the entry;
+                        a name that is not UTF-8 also comes as bytes in path_bytes or
+                        target_bytes
+    -duplicates         once the walk is done, print the regular files with the same content
+                        as others, a set at a time separated by blank lines; empty files
+                        are left out, and hard links to a file count as the file itself.
+                        --duplicates=json prints a JSON object per set with its size,
+                        SHA-256 and paths
+    -fprint FILE        print the path to FILE, which is created or truncated
+    -printf FORMAT      print FORMAT, with \\n, \\t, \\0, \\NNN escapes and the directives
+                        %p path          %f name            %h leading directories
+                        %P path below the starting point    %d depth
+                        %s size in bytes %m octal mode      %M symbolic mode
+                        %u user          %g group           %y type letter
+                        %t modification time, %a access, %c status change
+                        %T@ modification time in seconds since the epoch,
+                        %Tk part k of it as in strftime (%TY, %Tm...); %A and %C likewise
+                        and %% for %; a width and precision may follow %: %-10.3p
+    -prune              do not descend into this directory (no effect with -depth)
+    -exec CMD ;         run CMD with each {} replaced by the path; true if CMD succeeds
+    -exec CMD {} +      run CMD with as many paths as fit in place of {}; always true,
+                        but find_rs exits with status 1 if CMD fails
+    -execdir CMD ;      like -exec, run from the directory of the entry with {} as ./NAME;
+                        -execdir CMD {} + batches the entries of each directory
+    -ok CMD ;           like -exec, after asking on standard error; -okdir like -execdir
+    -delete             remove the file or empty directory, without following links;
+                        implies -depth, so it may not be used with -prune without -depth;
+                        not allowed with -H or -L
+
+  Where neither side of -a or -o has actions, the cheaper one runs first:
+  tests of the path, then of metadata, then of the contents of the file.
+
+  Two dashes are accepted in place of one (--name).";
+
+/// The entry for a link that walkdir could not follow because it is broken,
+/// which is still visited as a link.
+fn broken_link(e: &walkdir::Error, options: &Options) -> Option<Entry> {
+    let path = e.path()?;
+    let not_found = e.io_error()?.kind() == ErrorKind::NotFound;
+    let is_link = fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink());
+    if !(not_found && is_link && options.follow.at_depth(e.depth()))
+        || e.depth() < options.min_depth
+    {
+        return None;
+    }
+    Entry::new(path.to_path_buf(), e.depth(), true).ok()
+}
+
+/// Prints a diagnostic on standard error after the name of the program,
+/// as GNU find does.
+fn report(message: impl Display) {
+    eprintln!("find_rs: {}", message);
 }
 
-pub fn run(args: Args) -> MyResult<()> {
-    // In the Clap process, args.path contains multiple paths.
-    let type_filter = |entry: &DirEntry| {
-        args.r#type.is_empty()
-            || args.r#type.iter().any(|entry_type| match entry_type {
-                EntryType::Dir => entry.file_type().is_dir(),
-                EntryType::File => entry.file_type().is_file(),
-                EntryType::Link => entry.file_type().is_symlink(),
-            })
+/// Prints an error of the walk, a file system loop in the words of GNU find.
+fn report_walk_error(e: &walkdir::Error) {
+    match (e.loop_ancestor(), e.path()) {
+        (Some(ancestor), Some(path)) => report_loop(path, ancestor),
+        _ => report(e),
+    }
+}
+
+fn report_loop(path: &Path, ancestor: &Path) {
+    report(format_args!(
+        "File system loop detected; '{}' is part of the same file system loop as '{}'.",
+        path.display(),
+        ancestor.display()
+    ));
+}
+
+/// Stops the walk once standard output fails; a closed pipe, as when piped
+/// to `head`, ends it quietly.
+fn output_failed(e: io::Error) -> MyResult<bool> {
+    match e.kind() {
+        ErrorKind::BrokenPipe => Ok(false),
+        _ => Err(format!("standard output: {}", e).into()),
+    }
+}
+
+/// Prints matches as they are found. Returns false if an entry could not be
+/// visited, a command of `-exec` and friends failed to run, or a batched
+/// command failed, which calls for an exit status of 1.
+///
+/// With `-threads`, the tree is walked in parallel unless the expression runs
+/// commands or deletes files, or `-depth` is given.
+pub fn run(args: Args) -> MyResult<bool> {
+    let CommandLine {
+        paths,
+        expr,
+        options,
+        duplicates,
+    } = parse_command(&args.args)?;
+
+    let ignore = options
+        .respect_ignore
+        .then(|| IgnoreFilter::new(options.hidden));
+    let walked = match options.threads > 1 && !options.depth_first && !expr.needs_sequential_walk()
+    {
+        true => parallel::walk(&paths, &expr, &options, ignore.as_ref()),
+        false => walk(&paths, &expr, &options, ignore.as_ref()),
+    }?;
+
+    let Some(duplicates) = duplicates else {
+        return Ok(walked);
     };
+    let (sets, readable) = duplicates.sets();
+    let mut stdout = BufWriter::new(io::stdout());
+    match duplicates
+        .report(&sets, &mut stdout)
+        .and_then(|()| stdout.flush())
+    {
+        Ok(()) => Ok(walked && readable),
+        Err(e) => output_failed(e),
+    }
+}
 
-    let name_filter = |entry: &DirEntry| {
-        args.names.is_empty()
-            || args
-                .names
-                .iter()
-                .any(|re| re.is_match(&entry.file_name().to_string_lossy()))
+/// Walks `paths` on this thread, evaluating `expr` for each entry in turn.
+/// With `-sort`, the output is kept until the walk is done.
+fn walk(
+    paths: &[String],
+    expr: &Expr,
+    options: &Options,
+    ignore: Option<&IgnoreFilter>,
+) -> MyResult<bool> {
+    let Some(sort) = options.sort else {
+        return walk_with(
+            paths,
+            expr,
+            options,
+            ignore,
+            Context::default(),
+            |_, _, _| {},
+        );
     };
+    let mut results = Vec::new();
+    let succeeded = walk_with(
+        paths,
+        expr,
+        options,
+        ignore,
+        Context::new(Vec::new()),
+        |entry, start, output| {
+            if !output.is_empty() {
+                let key = SortKey::new(entry, start, Some(sort));
+                results.push((key, std::mem::take(output)));
+            }
+        },
+    )?;
+    results.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let mut stdout = BufWriter::new(io::stdout());
+    let written = results
+        .iter()
+        .try_for_each(|(_, output)| stdout.write_all(output))
+        .and_then(|()| stdout.flush());
+    match written {
+        Ok(()) => Ok(succeeded),
+        Err(e) => output_failed(e),
+    }
+}
+
+/// Walks `paths`, handing what each entry printed to `collect` along with
+/// the index of its starting point.
+fn walk_with<W: Write>(
+    paths: &[String],
+    expr: &Expr,
+    options: &Options,
+    ignore: Option<&IgnoreFilter>,
+    mut ctx: Context<W>,
+    mut collect: impl FnMut(&Entry, usize, &mut W),
+) -> MyResult<bool> {
+    for (start, path) in paths.iter().enumerate() {
+        // Ignored directories are skipped as they are reached, which must
+        // happen even above the minimum depth.
+        let min_depth = match ignore {
+            Some(_) => 0,
+            None => options.min_depth,
+        };
+        let mut walker = WalkDir::new(path)
+            .min_depth(min_depth)
+            .contents_first(options.depth_first)
+            .follow_links(options.follow == Follow::Always)
+            .follow_root_links(options.follow != Follow::Never)
+            .same_file_system(options.same_file_system);
+        if let Some(max_depth) = options.max_depth {
+            walker = walker.max_depth(max_depth);
+        }
+        if options.sorted {
+            walker = walker.sort_by_file_name();
+        }
 
-    for path in args.paths {
-        let entries = WalkDir::new(path)
-            .into_iter()
-            .filter_map(|e| match e {
-                Err(e) => {
-                    eprintln!("{}", e);
-                    None
+        let mut entries = walker.into_iter();
+        while let Some(entry) = entries.next() {
+            let entry = match entry {
+                Ok(entry) => Entry::from_walkdir(entry, options.follow),
+                Err(e) => match broken_link(&e, options) {
+                    Some(entry) => entry,
+                    None => {
+                        report_walk_error(&e);
+                        ctx.fail();
+                        continue;
+                    }
+                },
+            };
+            if let Some(ignore) = ignore {
+                let is_dir = entry.file_type().is_dir();
+                // With -depth the contents come first and must be checked
+                // against the directories above them.
+                let ignored = match options.depth_first {
+                    true => ignore.is_ignored_below(entry.path(), entry.depth(), is_dir),
+                    false => ignore.is_ignored(entry.path(), entry.depth(), is_dir),
+                };
+                if ignored {
+                    if is_dir && !options.depth_first {
+                        entries.skip_current_dir();
+                    }
+                    continue;
                 }
-                Ok(entry) => Some(entry),
-            })
-            .filter(type_filter)
-            .filter(name_filter)
-            .map(|entry| entry.path().display().to_string())
-            .collect::<Vec<String>>();
-
-        println!("{}", entries.join("\n"));
+                if entry.depth() < options.min_depth {
+                    continue;
+                }
+            }
+            ctx.prune = false;
+            expr.eval(&entry, &mut ctx);
+            collect(&entry, start, &mut ctx.output);
+            if let Some(e) = ctx.take_output_error() {
+                return output_failed(e);
+            }
+            // With -depth the contents have already been visited.
+            if ctx.prune && entry.file_type().is_dir() && !options.depth_first {
+                entries.skip_current_dir();
+            }
+        }
+    }
+    ctx.finish();
+    if let Some(e) = ctx.take_output_error() {
+        return output_failed(e);
     }
 
-    Ok(())
+    Ok(!ctx.failed())
 }
@@ -2,8 +2,12 @@ use clap::Parser;
 
 fn main() {
     let args = find_rs::Args::parse();
-    if let Err(e) = find_rs::run(args) {
-        eprintln!("{}", e);
-        std::process::exit(1);
+    match find_rs::run(args) {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        Err(e) => {
+            eprintln!("find_rs: {}", e);
+            std::process::exit(1);
+        }
     }
 }
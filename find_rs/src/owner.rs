@@ -0,0 +1,149 @@
+use std::path::Path;
+
+/// The kind of access tested by `-readable`, `-writable` and `-executable`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Access {
+    Read,
+    Write,
+    Execute,
+}
+
+#[cfg(unix)]
+mod imp {
+    use super::Access;
+    use std::ffi::{CStr, CString};
+    use std::mem::MaybeUninit;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+    use std::ptr;
+
+    /// Calls a reentrant getpw*_r/getgr*_r function, growing the buffer as
+    /// needed, and passes the entry it found to `extract` while the strings
+    /// it points to are still alive.
+    fn lookup<T, R>(
+        call: impl Fn(*mut T, &mut [libc::c_char], *mut *mut T) -> libc::c_int,
+        extract: impl FnOnce(&T) -> R,
+    ) -> Option<R> {
+        let mut buf = vec![0; 1024];
+        loop {
+            let mut entry = MaybeUninit::<T>::uninit();
+            let mut result = ptr::null_mut();
+            match call(entry.as_mut_ptr(), &mut buf, &mut result) {
+                0 if result.is_null() => return None,
+                0 => return Some(extract(unsafe { entry.assume_init_ref() })),
+                libc::ERANGE if buf.len() < 1 << 20 => buf.resize(buf.len() * 2, 0),
+                _ => return None,
+            }
+        }
+    }
+
+    fn to_string(name: *const libc::c_char) -> String {
+        unsafe { CStr::from_ptr(name) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    pub fn uid_by_name(name: &str) -> Option<u32> {
+        let name = CString::new(name).ok()?;
+        lookup(
+            |pwd, buf, result| unsafe {
+                libc::getpwnam_r(name.as_ptr(), pwd, buf.as_mut_ptr(), buf.len(), result)
+            },
+            |pwd: &libc::passwd| pwd.pw_uid,
+        )
+    }
+
+    pub fn gid_by_name(name: &str) -> Option<u32> {
+        let name = CString::new(name).ok()?;
+        lookup(
+            |grp, buf, result| unsafe {
+                libc::getgrnam_r(name.as_ptr(), grp, buf.as_mut_ptr(), buf.len(), result)
+            },
+            |grp: &libc::group| grp.gr_gid,
+        )
+    }
+
+    pub fn user_name(uid: u32) -> Option<String> {
+        lookup(
+            |pwd, buf, result| unsafe {
+                libc::getpwuid_r(uid, pwd, buf.as_mut_ptr(), buf.len(), result)
+            },
+            |pwd: &libc::passwd| to_string(pwd.pw_name),
+        )
+    }
+
+    pub fn group_name(gid: u32) -> Option<String> {
+        lookup(
+            |grp, buf, result| unsafe {
+                libc::getgrgid_r(gid, grp, buf.as_mut_ptr(), buf.len(), result)
+            },
+            |grp: &libc::group| to_string(grp.gr_name),
+        )
+    }
+
+    pub fn is_accessible(path: &Path, access: Access) -> bool {
+        let Ok(path) = CString::new(path.as_os_str().as_bytes()) else {
+            return false;
+        };
+        let mode = match access {
+            Access::Read => libc::R_OK,
+            Access::Write => libc::W_OK,
+            Access::Execute => libc::X_OK,
+        };
+        unsafe { libc::access(path.as_ptr(), mode) == 0 }
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use super::Access;
+    use std::path::Path;
+
+    pub fn uid_by_name(_name: &str) -> Option<u32> {
+        None
+    }
+
+    pub fn gid_by_name(_name: &str) -> Option<u32> {
+        None
+    }
+
+    pub fn user_name(_uid: u32) -> Option<String> {
+        None
+    }
+
+    pub fn group_name(_gid: u32) -> Option<String> {
+        None
+    }
+
+    pub fn is_accessible(path: &Path, access: Access) -> bool {
+        match path.metadata() {
+            Ok(metadata) => access != Access::Write || !metadata.permissions().readonly(),
+            Err(_) => false,
+        }
+    }
+}
+
+/// The id of the user `name`, which may also be given as a number.
+pub fn parse_user(name: &str) -> Option<u32> {
+    imp::uid_by_name(name).or_else(|| name.parse().ok())
+}
+
+/// The id of the group `name`, which may also be given as a number.
+pub fn parse_group(name: &str) -> Option<u32> {
+    imp::gid_by_name(name).or_else(|| name.parse().ok())
+}
+
+/// The name of the user `uid` in the password database.
+pub fn user_name(uid: u32) -> Option<String> {
+    imp::user_name(uid)
+}
+
+/// The name of the group `gid` in the group database.
+pub fn group_name(gid: u32) -> Option<String> {
+    imp::group_name(gid)
+}
+
+/// Whether the real user may access `path` as access(2) decides.
+pub fn is_accessible(path: &Path, access: Access) -> bool {
+    imp::is_accessible(path, access)
+}
@@ -0,0 +1,261 @@
+use crate::action::Context;
+use crate::entry::{Entry, Follow};
+use crate::expr::{Expr, Options};
+use crate::gitignore::IgnoreFilter;
+use crate::sort::SortKey;
+use crate::stat;
+use rayon::{Scope, ThreadPoolBuilder};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, SyncSender};
+use std::sync::Arc;
+use std::thread;
+
+type MyResult<T> = Result<T, Box<dyn Error>>;
+
+/// Output of the workers for the thread that writes standard output.
+enum Output {
+    /// What the entries of a directory printed, in the order they were read
+    Chunk(Vec<u8>),
+    /// What one entry printed, to be written in order once the walk is done
+    Sorted(SortKey, Vec<u8>),
+}
+
+/// A directory on the path from a starting point, to detect loops with `-L`.
+struct Ancestor {
+    id: (u64, u64),
+    path: PathBuf,
+    parent: Option<Arc<Ancestor>>,
+}
+
+/// A directory whose contents are still to be read.
+struct Dir {
+    path: PathBuf,
+    depth: usize,
+    /// The index of the starting point
+    start: usize,
+    /// The device of the starting point, with `-xdev`
+    root_dev: Option<u64>,
+    /// This directory and those above it, with `-L`
+    ancestors: Option<Arc<Ancestor>>,
+}
+
+struct Walker<'a> {
+    expr: &'a Expr,
+    options: &'a Options,
+    ignore: Option<&'a IgnoreFilter>,
+    sender: SyncSender<Output>,
+    /// Set once standard output is gone, which ends the walk
+    stop: AtomicBool,
+    failed: AtomicBool,
+}
+
+/// Walks `paths` on a pool of `options.threads` threads, each reading a
+/// directory at a time and evaluating the expression for its entries, while
+/// this thread writes what they print. Returns false if an entry could not be
+/// visited.
+pub fn walk(
+    paths: &[String],
+    expr: &Expr,
+    options: &Options,
+    ignore: Option<&IgnoreFilter>,
+) -> MyResult<bool> {
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(options.threads)
+        .build()?;
+    let (sender, receiver) = mpsc::sync_channel(options.threads * 16);
+
+    thread::scope(|s| {
+        let walk = s.spawn(move || {
+            let walker = Walker {
+                expr,
+                options,
+                ignore,
+                sender,
+                stop: AtomicBool::new(false),
+                failed: AtomicBool::new(false),
+            };
+            pool.scope(|scope| {
+                for (start, path) in paths.iter().enumerate() {
+                    walker.start(scope, start, path);
+                }
+            });
+            !walker.failed.into_inner()
+        });
+
+        let mut stdout = BufWriter::new(io::stdout());
+        let mut sorted = BTreeMap::new();
+        let mut written = Ok(());
+        for output in receiver {
+            match output {
+                Output::Chunk(bytes) => written = stdout.write_all(&bytes),
+                Output::Sorted(key, bytes) => {
+                    sorted.insert(key, bytes);
+                }
+            }
+            if written.is_err() {
+                break;
+            }
+        }
+        let succeeded = walk.join().unwrap_or(false);
+        let written = written
+            .and_then(|()| {
+                sorted
+                    .values()
+                    .try_for_each(|bytes| stdout.write_all(bytes))
+            })
+            .and_then(|()| stdout.flush());
+        match written {
+            Ok(()) => Ok(succeeded),
+            Err(e) => crate::output_failed(e),
+        }
+    })
+}
+
+impl<'a> Walker<'a> {
+    fn start<'s>(&'s self, scope: &Scope<'s>, start: usize, path: &str) {
+        let path = PathBuf::from(path);
+        let entry = match Entry::new(path.clone(), 0, self.options.follow.at_depth(0)) {
+            Ok(entry) => entry,
+            Err(e) => return self.report(&path, e),
+        };
+        let root_dev = match self.options.same_file_system {
+            true => fs::metadata(&path)
+                .ok()
+                .and_then(|m| stat::inode(&m))
+                .map(|(dev, _)| dev),
+            false => None,
+        };
+        let mut ctx = Context::new(Vec::new());
+        self.visit(scope, &mut ctx, entry, start, root_dev, None);
+        self.failed.fetch_or(ctx.failed(), Ordering::Relaxed);
+        self.send(Output::Chunk(ctx.output));
+    }
+
+    /// Reads the entries of `dir`, visiting each one.
+    fn read_dir<'s>(&'s self, scope: &Scope<'s>, dir: Dir) {
+        if self.stop.load(Ordering::Relaxed) {
+            return;
+        }
+        let entries = match fs::read_dir(&dir.path) {
+            Ok(entries) => entries,
+            Err(e) => return self.report(&dir.path, e),
+        };
+        let mut ctx = Context::new(Vec::new());
+        for entry in entries {
+            let depth = dir.depth + 1;
+            let entry =
+                match entry.and_then(|e| Entry::from_dir_entry(&e, depth, self.options.follow)) {
+                    Ok(entry) => entry,
+                    Err(e) => {
+                        self.report(&dir.path, e);
+                        continue;
+                    }
+                };
+            self.visit(
+                scope,
+                &mut ctx,
+                entry,
+                dir.start,
+                dir.root_dev,
+                dir.ancestors.as_ref(),
+            );
+        }
+        self.failed.fetch_or(ctx.failed(), Ordering::Relaxed);
+        self.send(Output::Chunk(ctx.output));
+    }
+
+    /// Evaluates the expression for `entry`, then spawns the reading of its
+    /// contents if it is a directory to descend into.
+    fn visit<'s>(
+        &'s self,
+        scope: &Scope<'s>,
+        ctx: &mut Context<Vec<u8>>,
+        entry: Entry,
+        start: usize,
+        root_dev: Option<u64>,
+        ancestors: Option<&Arc<Ancestor>>,
+    ) {
+        let is_dir = entry.file_type().is_dir();
+        if let Some(ignore) = self.ignore {
+            if ignore.is_ignored(entry.path(), entry.depth(), is_dir) {
+                return;
+            }
+        }
+        let id = match is_dir {
+            true => entry.metadata().ok().and_then(|m| stat::inode(&m)),
+            false => None,
+        };
+        let follow_all = self.options.follow == Follow::Always;
+        if let (true, Some(id)) = (follow_all, id) {
+            let mut ancestor = ancestors;
+            while let Some(dir) = ancestor {
+                if dir.id == id {
+                    crate::report_loop(entry.path(), &dir.path);
+                    self.failed.store(true, Ordering::Relaxed);
+                    return;
+                }
+                ancestor = dir.parent.as_ref();
+            }
+        }
+
+        ctx.prune = false;
+        if entry.depth() >= self.options.min_depth {
+            self.expr.eval(&entry, ctx);
+            let buffered = self.options.sorted || self.options.sort.is_some();
+            if buffered && !ctx.output.is_empty() {
+                let key = SortKey::new(&entry, start, self.options.sort);
+                self.send(Output::Sorted(key, std::mem::take(&mut ctx.output)));
+            }
+        }
+
+        let descend = is_dir
+            && !ctx.prune
+            && self.options.max_depth.is_none_or(|max| entry.depth() < max)
+            && root_dev.is_none_or(|dev| id.is_some_and(|(d, _)| d == dev));
+        if !descend {
+            return;
+        }
+        let ancestors = match (follow_all, id) {
+            (true, Some(id)) => Some(Arc::new(Ancestor {
+                id,
+                path: entry.path().to_path_buf(),
+                parent: ancestors.cloned(),
+            })),
+            _ => None,
+        };
+        let dir = Dir {
+            path: entry.path().to_path_buf(),
+            depth: entry.depth(),
+            start,
+            root_dev,
+            ancestors,
+        };
+        scope.spawn(move |scope| self.read_dir(scope, dir));
+    }
+
+    fn send(&self, output: Output) {
+        if let Output::Chunk(bytes) = &output {
+            if bytes.is_empty() {
+                return;
+            }
+        }
+        if self.sender.send(output).is_err() {
+            self.stop.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Prints an error reading `path` as walkdir words it.
+    fn report(&self, path: &Path, e: io::Error) {
+        crate::report(format_args!(
+            "IO error for operation on {}: {}",
+            path.display(),
+            e
+        ));
+        self.failed.store(true, Ordering::Relaxed);
+    }
+}
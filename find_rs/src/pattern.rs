@@ -0,0 +1,247 @@
+use clap::ValueEnum;
+use regex::{Regex, RegexBuilder};
+
+/// A shell pattern as understood by fnmatch(3) without any flags:
+/// `*` and `?` also match `/` and a leading `.`.
+#[derive(Debug, Clone)]
+pub struct Glob {
+    regex: Regex,
+}
+
+impl Glob {
+    pub fn new(pattern: &str, case_insensitive: bool) -> Result<Glob, regex::Error> {
+        let regex = RegexBuilder::new(&format!("(?s)^{}$", glob_to_regex(pattern)))
+            .case_insensitive(case_insensitive)
+            .build()?;
+        Ok(Glob { regex })
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.regex.is_match(text)
+    }
+}
+
+/// Regular expression syntax accepted by `--regex` and `--iregex`.
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq)]
+pub enum RegexType {
+    /// GNU Emacs syntax: `\(` `\)` group and `\|` alternates; the default of GNU find
+    Emacs,
+    /// POSIX basic regular expressions
+    PosixBasic,
+    /// POSIX extended regular expressions
+    PosixExtended,
+    /// The syntax of the Rust regex crate
+    Rust,
+}
+
+/// Compiles `pattern` in the given dialect so that it must match the whole text.
+pub fn new_regex(
+    pattern: &str,
+    regex_type: RegexType,
+    case_insensitive: bool,
+) -> Result<Regex, regex::Error> {
+    let translated = match regex_type {
+        RegexType::Rust => pattern.to_string(),
+        _ => posix_to_regex(pattern, regex_type),
+    };
+    RegexBuilder::new(&format!("(?s)^(?:{})$", translated))
+        .case_insensitive(case_insensitive)
+        .build()
+}
+
+fn glob_to_regex(pattern: &str) -> String {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut regex = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            '[' => match bracket_to_regex(&chars[i..], &['!', '^'], true) {
+                Some((class, len)) => {
+                    regex.push_str(&class);
+                    i += len;
+                    continue;
+                }
+                None => regex.push_str("\\["),
+            },
+            '\\' if i + 1 < chars.len() => {
+                i += 1;
+                push_literal(&mut regex, chars[i]);
+            }
+            c => push_literal(&mut regex, c),
+        }
+        i += 1;
+    }
+
+    regex
+}
+
+fn posix_to_regex(pattern: &str, regex_type: RegexType) -> String {
+    let basic = regex_type == RegexType::PosixBasic;
+    let extended = regex_type == RegexType::PosixExtended;
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut regex = String::new();
+    // Whether a `*` here has nothing to repeat and is therefore literal.
+    let mut at_start = true;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let mut next_at_start = false;
+        match c {
+            '[' => match bracket_to_regex(&chars[i..], &['^'], false) {
+                Some((class, len)) => {
+                    regex.push_str(&class);
+                    i += len;
+                    at_start = false;
+                    continue;
+                }
+                // Left unbalanced so that compiling reports the error.
+                None => regex.push('['),
+            },
+            '\\' if i + 1 < chars.len() => {
+                i += 1;
+                let escaped = chars[i];
+                match escaped {
+                    '(' | ')' | '|' | '{' | '}' if !extended => {
+                        regex.push(escaped);
+                        next_at_start = escaped == '(' || escaped == '|';
+                    }
+                    '+' | '?' if basic => regex.push(escaped),
+                    '<' | '>' => regex.push_str(if escaped == '<' {
+                        "\\b{start}"
+                    } else {
+                        "\\b{end}"
+                    }),
+                    '`' => regex.push_str("\\A"),
+                    '\'' => regex.push_str("\\z"),
+                    'w' | 'W' | 's' | 'S' | 'b' | 'B' => {
+                        regex.push('\\');
+                        regex.push(escaped);
+                    }
+                    _ => push_literal(&mut regex, escaped),
+                }
+            }
+            '*' if at_start => regex.push_str("\\*"),
+            '(' | ')' | '|' | '{' | '}' if !extended => push_literal(&mut regex, c),
+            '+' | '?' if basic => push_literal(&mut regex, c),
+            '+' | '?' if at_start => push_literal(&mut regex, c),
+            '.' | '*' | '+' | '?' | '^' | '$' | '(' | ')' | '|' | '{' | '}' => {
+                regex.push(c);
+                next_at_start = c == '^' || c == '(' || c == '|';
+            }
+            _ => push_literal(&mut regex, c),
+        }
+        at_start = next_at_start;
+        i += 1;
+    }
+
+    regex
+}
+
+/// Translates the bracket expression at the start of `chars` into a Rust
+/// character class, returning it with the number of chars consumed, or
+/// `None` if the bracket is not closed. Backslash escapes the next character
+/// only in shell patterns; in POSIX regular expressions it is literal.
+fn bracket_to_regex(chars: &[char], negations: &[char], escapes: bool) -> Option<(String, usize)> {
+    let mut class = String::from("[");
+    let mut i = 1;
+
+    if i < chars.len() && negations.contains(&chars[i]) {
+        class.push('^');
+        i += 1;
+    }
+
+    let first = i;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == ']' && i > first {
+            class.push(']');
+            return Some((class, i + 1));
+        }
+        if c == '[' && chars.get(i + 1) == Some(&':') {
+            if let Some(end) = (i + 2..chars.len().saturating_sub(1))
+                .find(|&j| chars[j] == ':' && chars[j + 1] == ']')
+            {
+                class.extend(&chars[i..end + 2]);
+                i = end + 2;
+                continue;
+            }
+        }
+        if c == '-' && i > first && chars.get(i + 1).is_some_and(|&n| n != ']') {
+            class.push('-');
+        } else if c == '\\' && escapes && i + 1 < chars.len() {
+            i += 1;
+            push_class_literal(&mut class, chars[i]);
+        } else {
+            push_class_literal(&mut class, c);
+        }
+        i += 1;
+    }
+
+    None
+}
+
+fn push_literal(regex: &mut String, c: char) {
+    regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4])));
+}
+
+fn push_class_literal(class: &mut String, c: char) {
+    if c.is_ascii_punctuation() {
+        class.push('\\');
+    }
+    class.push(c);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{new_regex, Glob, RegexType};
+
+    #[test]
+    fn test_glob() {
+        let glob = Glob::new("*.csv", false).unwrap();
+        assert!(glob.is_match("b.csv"));
+        assert!(glob.is_match(".csv"));
+        assert!(!glob.is_match("b.csv.bak"));
+
+        let glob = Glob::new("[!a-c]?\\*", false).unwrap();
+        assert!(glob.is_match("dx*"));
+        assert!(!glob.is_match("bx*"));
+        assert!(!glob.is_match("dxy"));
+
+        let glob = Glob::new("[]x]", false).unwrap();
+        assert!(glob.is_match("]"));
+        assert!(glob.is_match("x"));
+
+        assert!(Glob::new("[abc", false).unwrap().is_match("[abc"));
+        assert!(Glob::new("A*", true).unwrap().is_match("a.txt"));
+    }
+
+    #[test]
+    fn test_regex_dialects() {
+        let re = new_regex(r"a\(b\|c\)+", RegexType::Emacs, false).unwrap();
+        assert!(re.is_match("abcb"));
+        assert!(!re.is_match("xabc"));
+
+        let re = new_regex(r"a(b|c)+", RegexType::Emacs, false).unwrap();
+        assert!(re.is_match("a(b|c)))"));
+
+        let re = new_regex(r"a\{2\}+", RegexType::PosixBasic, false).unwrap();
+        assert!(re.is_match("aa+"));
+
+        let re = new_regex(r"*a|b", RegexType::PosixExtended, false).unwrap();
+        assert!(re.is_match("*a"));
+        assert!(re.is_match("b"));
+
+        let re = new_regex(r"[\d]", RegexType::PosixExtended, false).unwrap();
+        assert!(re.is_match("\\"));
+        assert!(!re.is_match("1"));
+
+        let re = new_regex(r"\d+", RegexType::Rust, false).unwrap();
+        assert!(re.is_match("123"));
+
+        assert!(new_regex("[", RegexType::Emacs, false).is_err());
+    }
+}
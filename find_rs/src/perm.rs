@@ -0,0 +1,179 @@
+/// How the permission bits of a file are compared with the mode of `-perm`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PermMatch {
+    /// `-perm MODE`: the bits are exactly MODE
+    Exact,
+    /// `-perm -MODE`: all of the bits of MODE are set
+    All,
+    /// `-perm /MODE`: any of the bits of MODE is set, or MODE is 0
+    Any,
+}
+
+/// The argument of `-perm`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Perm {
+    pub mode: u32,
+    pub kind: PermMatch,
+}
+
+impl Perm {
+    /// Parses `MODE`, `-MODE` or `/MODE`, where MODE is octal or symbolic as
+    /// accepted by chmod(1), applied to a mode of 0.
+    pub fn parse(arg: &str) -> Option<Perm> {
+        let (kind, mode) = match arg.as_bytes().first() {
+            Some(b'-') => (PermMatch::All, &arg[1..]),
+            Some(b'/') => (PermMatch::Any, &arg[1..]),
+            _ => (PermMatch::Exact, arg),
+        };
+        parse_mode(mode).map(|mode| Perm { mode, kind })
+    }
+
+    /// Tests the permission bits (including set-id and sticky) of `mode`.
+    pub fn matches(self, mode: u32) -> bool {
+        let mode = mode & 0o7777;
+        match self.kind {
+            PermMatch::Exact => mode == self.mode,
+            PermMatch::All => mode & self.mode == self.mode,
+            PermMatch::Any => self.mode == 0 || mode & self.mode != 0,
+        }
+    }
+}
+
+/// Permission bits as ls(1) shows them after the type letter, such as
+/// `drwxr-xr-x`; a regular file is `-`.
+pub fn symbolic_mode(mode: u32, type_char: char) -> String {
+    let mut text = String::with_capacity(10);
+    text.push(match type_char {
+        'f' | 'U' => '-',
+        other => other,
+    });
+    // Each class with the bit that replaces its execute letter: s, s, t.
+    for (shift, special, letter) in [(6, 0o4000, 's'), (3, 0o2000, 's'), (0, 0o1000, 't')] {
+        let rwx = (mode >> shift) & 0o7;
+        text.push(if rwx & 4 != 0 { 'r' } else { '-' });
+        text.push(if rwx & 2 != 0 { 'w' } else { '-' });
+        text.push(match (rwx & 1 != 0, mode & special != 0) {
+            (true, true) => letter,
+            (false, true) => letter.to_ascii_uppercase(),
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+    text
+}
+
+fn parse_mode(mode: &str) -> Option<u32> {
+    if mode.is_empty() {
+        return None;
+    }
+    if mode.bytes().all(|b| b.is_ascii_digit()) {
+        return u32::from_str_radix(mode, 8).ok().filter(|&m| m <= 0o7777);
+    }
+    mode.split(',').try_fold(0, apply_clause)
+}
+
+/// Applies a symbolic clause such as `ug+rw` or `o=u` to `mode`.
+fn apply_clause(mut mode: u32, clause: &str) -> Option<u32> {
+    let op_start = clause.find(['+', '-', '='])?;
+    let (who, mut rest) = clause.split_at(op_start);
+    let mut who_mask = 0;
+    for c in who.chars() {
+        who_mask |= match c {
+            'u' => 0o4700,
+            'g' => 0o2070,
+            'o' => 0o1007,
+            'a' => 0o7777,
+            _ => return None,
+        };
+    }
+    if who_mask == 0 {
+        who_mask = 0o7777;
+    }
+
+    while let Some(op) = rest.chars().next() {
+        if !"+-=".contains(op) {
+            return None;
+        }
+        let end = rest[1..]
+            .find(['+', '-', '='])
+            .map_or(rest.len(), |i| i + 1);
+        let perms = &rest[1..end];
+        rest = &rest[end..];
+
+        let bits = match perms {
+            "u" => copy_class(mode, 6),
+            "g" => copy_class(mode, 3),
+            "o" => copy_class(mode, 0),
+            _ => {
+                let mut bits = 0;
+                for c in perms.chars() {
+                    bits |= match c {
+                        'r' => 0o444,
+                        'w' => 0o222,
+                        'x' => 0o111,
+                        // Execute only if someone may already execute: never a directory here.
+                        'X' if mode & 0o111 != 0 => 0o111,
+                        'X' => 0,
+                        's' => 0o6000,
+                        't' => 0o1000,
+                        _ => return None,
+                    };
+                }
+                bits
+            }
+        } & who_mask;
+
+        mode = match op {
+            '+' => mode | bits,
+            '-' => mode & !bits,
+            _ => (mode & !who_mask) | bits,
+        };
+    }
+    Some(mode)
+}
+
+/// The `rwx` bits of the class at `shift`, repeated for every class.
+fn copy_class(mode: u32, shift: u32) -> u32 {
+    let rwx = (mode >> shift) & 0o7;
+    rwx << 6 | rwx << 3 | rwx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{symbolic_mode, Perm, PermMatch};
+
+    #[test]
+    fn test_parse_perm() {
+        let perm = |mode, kind| Some(Perm { mode, kind });
+        assert_eq!(Perm::parse("644"), perm(0o644, PermMatch::Exact));
+        assert_eq!(Perm::parse("-o+w"), perm(0o002, PermMatch::All));
+        assert_eq!(Perm::parse("/u=x,g=x"), perm(0o110, PermMatch::Any));
+        assert_eq!(Perm::parse("u=rwx,go=u-w"), perm(0o755, PermMatch::Exact));
+        assert_eq!(Perm::parse("+x"), perm(0o111, PermMatch::Exact));
+        assert_eq!(Perm::parse("u+s,+t"), perm(0o5000, PermMatch::Exact));
+        assert_eq!(Perm::parse("a=X"), perm(0, PermMatch::Exact));
+        assert_eq!(Perm::parse("10000"), None);
+        assert_eq!(Perm::parse("u+q"), None);
+        assert_eq!(Perm::parse("rw"), None);
+        assert_eq!(Perm::parse("-"), None);
+    }
+
+    #[test]
+    fn test_perm_matches() {
+        assert!(Perm::parse("644").unwrap().matches(0o100644));
+        assert!(!Perm::parse("644").unwrap().matches(0o100664));
+        assert!(Perm::parse("-022").unwrap().matches(0o777));
+        assert!(!Perm::parse("-022").unwrap().matches(0o755));
+        assert!(Perm::parse("/022").unwrap().matches(0o642));
+        assert!(!Perm::parse("/022").unwrap().matches(0o644));
+        assert!(Perm::parse("/000").unwrap().matches(0o400));
+    }
+
+    #[test]
+    fn test_symbolic_mode() {
+        assert_eq!(symbolic_mode(0o755, 'd'), "drwxr-xr-x");
+        assert_eq!(symbolic_mode(0o644, 'f'), "-rw-r--r--");
+        assert_eq!(symbolic_mode(0o4755, 'f'), "-rwsr-xr-x");
+        assert_eq!(symbolic_mode(0o1776, 'd'), "drwxrwxrwT");
+    }
+}
@@ -0,0 +1,362 @@
+use crate::content;
+use crate::entry::Entry;
+use crate::owner::{self, Access};
+use crate::pattern::Glob;
+use crate::perm::Perm;
+use crate::stat::{self, Comparison, TimeField};
+use crate::EntryType;
+use regex::Regex;
+use std::fs::{self, FileType};
+use std::time::SystemTime;
+
+/// A test on a single entry, without side effects.
+#[derive(Debug)]
+pub enum Predicate {
+    True,
+    False,
+    /// `-name`, `-iname`: the last component of the path matches a shell pattern
+    Name(Glob),
+    /// `-path`, `-ipath`: the whole path matches a shell pattern
+    Path(Glob),
+    /// `-regex`, `-iregex`: the whole path matches a regular expression
+    Regex(Regex),
+    /// `-type`: one of the types
+    Type(Vec<EntryType>),
+    /// `-xtype`: one of the types, with links resolved as the other of `-P` and `-L` would
+    XType(Vec<EntryType>),
+    /// `-size`: the size in units of the given number of bytes, rounded up
+    Size(Comparison, u64),
+    /// `-empty`: an empty regular file or directory
+    Empty,
+    /// `-atime`, `-amin`, `-ctime`, ...: units of `unit` seconds since the timestamp,
+    /// in whole days, or in minutes rounded up for an exact number
+    Age {
+        field: TimeField,
+        comparison: Comparison,
+        unit: u64,
+        now: SystemTime,
+    },
+    /// `-newer`, `-anewer`, `-cnewer`, `-newerXY`: the timestamp is later than the reference
+    Newer(TimeField, SystemTime),
+    /// `-uid`, `-user`: the numeric owner
+    Uid(Comparison),
+    /// `-gid`, `-group`: the numeric group
+    Gid(Comparison),
+    /// `-nouser`: no user in the password database owns the file
+    NoUser,
+    /// `-nogroup`: the file's group is not in the group database
+    NoGroup,
+    /// `-perm`
+    Perm(Perm),
+    /// `-readable`, `-writable`, `-executable`: access(2) grants the access
+    Accessible(Access),
+    /// `-inum`: the inode number
+    Inum(Comparison),
+    /// `-samefile`: the same device and inode as the reference file
+    SameFile(u64, u64),
+    /// `-links`: the number of hard links
+    Links(Comparison),
+    /// `-contains`: a line of the regular file matches a regular expression
+    Contains(regex::bytes::Regex),
+    /// `-mime`: the media type sniffed from the first bytes matches a shell pattern
+    Mime(Glob),
+}
+
+/// What evaluating a predicate takes, to try cheaper ones first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Cost {
+    /// Only the path and the type the walk found
+    Path,
+    /// A system call, such as stat(2)
+    Stat,
+    /// Reading the start of the file
+    Sniff,
+    /// Reading the whole file
+    Read,
+}
+
+impl Predicate {
+    pub fn eval(&self, entry: &Entry) -> bool {
+        match self {
+            Predicate::True => true,
+            Predicate::False => false,
+            Predicate::Name(glob) => glob.is_match(&entry.file_name().to_string_lossy()),
+            Predicate::Path(glob) => glob.is_match(&entry.path().to_string_lossy()),
+            Predicate::Regex(re) => re.is_match(&entry.path().to_string_lossy()),
+            Predicate::Type(types) => is_type(types, entry.file_type()),
+            Predicate::XType(types) => is_type(types, entry.xtype()),
+            Predicate::Size(comparison, unit) => entry
+                .metadata()
+                .is_ok_and(|m| comparison.matches(stat::size_in_units(m.len(), *unit))),
+            Predicate::Empty => {
+                if entry.file_type().is_dir() {
+                    fs::read_dir(entry.path()).is_ok_and(|mut dir| dir.next().is_none())
+                } else {
+                    entry.file_type().is_file() && entry.metadata().is_ok_and(|m| m.len() == 0)
+                }
+            }
+            Predicate::Age {
+                field,
+                comparison,
+                unit,
+                now,
+            } => entry
+                .metadata()
+                .ok()
+                .and_then(|m| field.of(&m))
+                .is_some_and(|time| stat::age_matches(*comparison, *now, time, *unit)),
+            Predicate::Newer(field, reference) => entry
+                .metadata()
+                .ok()
+                .and_then(|m| field.of(&m))
+                .is_some_and(|time| time > *reference),
+            Predicate::Uid(comparison) => {
+                owner_of(entry).is_some_and(|(uid, _)| comparison.matches(i64::from(uid)))
+            }
+            Predicate::Gid(comparison) => {
+                owner_of(entry).is_some_and(|(_, gid)| comparison.matches(i64::from(gid)))
+            }
+            Predicate::NoUser => {
+                owner_of(entry).is_some_and(|(uid, _)| owner::user_name(uid).is_none())
+            }
+            Predicate::NoGroup => {
+                owner_of(entry).is_some_and(|(_, gid)| owner::group_name(gid).is_none())
+            }
+            Predicate::Perm(perm) => entry.metadata().is_ok_and(|m| perm.matches(stat::mode(&m))),
+            Predicate::Accessible(access) => owner::is_accessible(entry.path(), *access),
+            Predicate::Inum(comparison) => {
+                inode_of(entry).is_some_and(|(_, ino)| comparison.matches(ino as i64))
+            }
+            Predicate::SameFile(dev, ino) => inode_of(entry) == Some((*dev, *ino)),
+            Predicate::Links(comparison) => entry
+                .metadata()
+                .ok()
+                .and_then(|m| stat::links(&m))
+                .is_some_and(|links| comparison.matches(links as i64)),
+            Predicate::Contains(re) => {
+                entry.file_type().is_file() && content::contains(entry.path(), re)
+            }
+            Predicate::Mime(glob) => glob.is_match(content::mime_type(entry)),
+        }
+    }
+
+    pub(crate) fn cost(&self) -> Cost {
+        match self {
+            Predicate::True
+            | Predicate::False
+            | Predicate::Name(_)
+            | Predicate::Path(_)
+            | Predicate::Regex(_)
+            | Predicate::Type(_) => Cost::Path,
+            Predicate::Mime(_) => Cost::Sniff,
+            Predicate::Contains(_) => Cost::Read,
+            _ => Cost::Stat,
+        }
+    }
+}
+
+fn is_type(types: &[EntryType], file_type: FileType) -> bool {
+    let letter = stat::type_char(file_type);
+    types.iter().any(|entry_type| entry_type.letter() == letter)
+}
+
+fn inode_of(entry: &Entry) -> Option<(u64, u64)> {
+    entry.metadata().ok().and_then(|m| stat::inode(&m))
+}
+
+fn owner_of(entry: &Entry) -> Option<(u32, u32)> {
+    entry.metadata().ok().and_then(|m| stat::owner(&m))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Predicate;
+    use crate::entry::Entry;
+    use crate::owner::Access;
+    use crate::pattern::Glob;
+    use crate::perm::Perm;
+    use crate::stat::{self, Comparison, TimeField};
+    use crate::test_fixture::{entry, fixture};
+    use crate::EntryType;
+    use regex::Regex;
+    use std::fs;
+    use std::time::Duration;
+
+    #[test]
+    fn test_true_false() {
+        let tmp = fixture();
+        let notes = entry(tmp.path().join("notes.txt"));
+        assert!(Predicate::True.eval(&notes));
+        assert!(!Predicate::False.eval(&notes));
+    }
+
+    #[test]
+    fn test_name() {
+        let tmp = fixture();
+        let notes = entry(tmp.path().join("notes.txt"));
+        assert!(Predicate::Name(Glob::new("*.txt", false).unwrap()).eval(&notes));
+        assert!(Predicate::Name(Glob::new("NOTES.*", true).unwrap()).eval(&notes));
+        assert!(!Predicate::Name(Glob::new("NOTES.*", false).unwrap()).eval(&notes));
+        // Only the last component is matched.
+        assert!(!Predicate::Name(Glob::new("*/notes.txt", false).unwrap()).eval(&notes));
+    }
+
+    #[test]
+    fn test_path() {
+        let tmp = fixture();
+        let inner = entry(tmp.path().join("dir/inner"));
+        assert!(Predicate::Path(Glob::new("*/dir/inner", false).unwrap()).eval(&inner));
+        assert!(!Predicate::Path(Glob::new("dir/inner", false).unwrap()).eval(&inner));
+    }
+
+    #[test]
+    fn test_regex() {
+        let tmp = fixture();
+        let inner = entry(tmp.path().join("dir/inner"));
+        assert!(Predicate::Regex(Regex::new("^.*/dir/[a-z]+$").unwrap()).eval(&inner));
+        assert!(!Predicate::Regex(Regex::new("^dir/").unwrap()).eval(&inner));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_type_xtype() {
+        let tmp = fixture();
+        let link = tmp.path().join("link");
+        std::os::unix::fs::symlink("dir", &link).unwrap();
+
+        let unfollowed = entry(&link);
+        assert!(Predicate::Type(vec![EntryType::Link]).eval(&unfollowed));
+        assert!(!Predicate::Type(vec![EntryType::Dir]).eval(&unfollowed));
+        assert!(Predicate::XType(vec![EntryType::Dir]).eval(&unfollowed));
+
+        let followed = Entry::new(link, 1, true).unwrap();
+        assert!(Predicate::Type(vec![EntryType::File, EntryType::Dir]).eval(&followed));
+        assert!(Predicate::XType(vec![EntryType::Link]).eval(&followed));
+    }
+
+    #[test]
+    fn test_size() {
+        let tmp = fixture();
+        let data = entry(tmp.path().join("data.bin"));
+        assert!(Predicate::Size(Comparison::Exactly(1000), 1).eval(&data));
+        // Rounded up to whole 512-byte blocks.
+        assert!(Predicate::Size(Comparison::Exactly(2), 512).eval(&data));
+        assert!(Predicate::Size(Comparison::Less(2), 1024).eval(&data));
+        assert!(!Predicate::Size(Comparison::Greater(1), 1024).eval(&data));
+    }
+
+    #[test]
+    fn test_empty() {
+        let tmp = fixture();
+        assert!(Predicate::Empty.eval(&entry(tmp.path().join("empty"))));
+        assert!(Predicate::Empty.eval(&entry(tmp.path().join("hollow"))));
+        assert!(!Predicate::Empty.eval(&entry(tmp.path().join("notes.txt"))));
+        assert!(!Predicate::Empty.eval(&entry(tmp.path().join("dir"))));
+    }
+
+    #[test]
+    fn test_age_newer() {
+        let tmp = fixture();
+        let notes = entry(tmp.path().join("notes.txt"));
+        let mtime = notes.metadata().unwrap().modified().unwrap();
+        let age = |comparison| Predicate::Age {
+            field: TimeField::Modify,
+            comparison,
+            unit: 86400,
+            now: mtime + Duration::from_secs(2 * 86400 + 60),
+        };
+        assert!(age(Comparison::Exactly(2)).eval(&notes));
+        assert!(age(Comparison::Greater(1)).eval(&notes));
+        assert!(!age(Comparison::Less(2)).eval(&notes));
+
+        let second = Duration::from_secs(1);
+        assert!(Predicate::Newer(TimeField::Modify, mtime - second).eval(&notes));
+        assert!(!Predicate::Newer(TimeField::Modify, mtime + second).eval(&notes));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_uid_gid_nouser_nogroup() {
+        let tmp = fixture();
+        let notes = entry(tmp.path().join("notes.txt"));
+        let (uid, gid) = stat::owner(&notes.metadata().unwrap()).unwrap();
+        assert!(Predicate::Uid(Comparison::Exactly(i64::from(uid))).eval(&notes));
+        assert!(!Predicate::Uid(Comparison::Greater(i64::from(uid))).eval(&notes));
+        assert!(Predicate::Gid(Comparison::Exactly(i64::from(gid))).eval(&notes));
+        assert!(!Predicate::Gid(Comparison::Less(i64::from(gid))).eval(&notes));
+        // The user running the tests owns the file.
+        assert!(!Predicate::NoUser.eval(&notes));
+        assert!(!Predicate::NoGroup.eval(&notes));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_perm() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = fixture();
+        let path = tmp.path().join("notes.txt");
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+        let notes = entry(path);
+        assert!(Predicate::Perm(Perm::parse("640").unwrap()).eval(&notes));
+        assert!(Predicate::Perm(Perm::parse("-u+r").unwrap()).eval(&notes));
+        assert!(!Predicate::Perm(Perm::parse("-o+r").unwrap()).eval(&notes));
+        assert!(Predicate::Perm(Perm::parse("/o+r,g+r").unwrap()).eval(&notes));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_accessible() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = fixture();
+        let path = tmp.path().join("notes.txt");
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        let notes = entry(path);
+        assert!(Predicate::Accessible(Access::Read).eval(&notes));
+        // Not even root may execute a file without any execute bit.
+        assert!(!Predicate::Accessible(Access::Execute).eval(&notes));
+
+        let gone = entry(tmp.path().join("empty"));
+        fs::remove_file(gone.path()).unwrap();
+        assert!(!Predicate::Accessible(Access::Read).eval(&gone));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_inum_samefile_links() {
+        let tmp = fixture();
+        let path = tmp.path().join("notes.txt");
+        fs::hard_link(&path, tmp.path().join("hard")).unwrap();
+        let notes = entry(path);
+        let hard = entry(tmp.path().join("hard"));
+        let (dev, ino) = stat::inode(&notes.metadata().unwrap()).unwrap();
+
+        assert!(Predicate::Inum(Comparison::Exactly(ino as i64)).eval(&hard));
+        assert!(Predicate::SameFile(dev, ino).eval(&hard));
+        assert!(!Predicate::SameFile(dev, ino).eval(&entry(tmp.path().join("empty"))));
+        assert!(Predicate::Links(Comparison::Exactly(2)).eval(&notes));
+        assert!(Predicate::Links(Comparison::Less(2)).eval(&entry(tmp.path().join("empty"))));
+    }
+
+    #[test]
+    fn test_contains() {
+        let tmp = fixture();
+        let notes = entry(tmp.path().join("notes.txt"));
+        let contains = |re| Predicate::Contains(regex::bytes::Regex::new(re).unwrap());
+        assert!(contains("^beta$").eval(&notes));
+        assert!(!contains("alpha.beta").eval(&notes));
+        // Only regular files are read.
+        assert!(!contains("").eval(&entry(tmp.path().join("dir"))));
+    }
+
+    #[test]
+    fn test_mime() {
+        let tmp = fixture();
+        let mime = |pattern| Predicate::Mime(Glob::new(pattern, true).unwrap());
+        assert!(mime("text/*").eval(&entry(tmp.path().join("notes.txt"))));
+        assert!(mime("application/octet-stream").eval(&entry(tmp.path().join("data.bin"))));
+        assert!(mime("inode/x-empty").eval(&entry(tmp.path().join("empty"))));
+        assert!(mime("INODE/DIRECTORY").eval(&entry(tmp.path().join("dir"))));
+    }
+}
@@ -0,0 +1,53 @@
+use crate::entry::Entry;
+use clap::ValueEnum;
+use std::cmp::Reverse;
+use std::ffi::OsString;
+use std::time::UNIX_EPOCH;
+
+/// The order of `-sort`, in which the results are printed once the walk is done.
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq)]
+pub enum SortBy {
+    /// By path, a directory before its contents
+    Name,
+    /// Largest first, as `ls -S`
+    Size,
+    /// Most recently modified first, as `ls -t`
+    Mtime,
+}
+
+/// Where the output of an entry goes among the others: by the field of
+/// `-sort`, then by starting point and the names below it, which is also
+/// the order of `-sorted`.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SortKey {
+    field: Reverse<i128>,
+    start: usize,
+    names: Vec<OsString>,
+}
+
+impl SortKey {
+    /// The key of `entry`, found below the starting point numbered `start`.
+    pub fn new(entry: &Entry, start: usize, sort: Option<SortBy>) -> SortKey {
+        let field = match sort {
+            Some(SortBy::Size) => entry.metadata().map_or(0, |m| m.len() as i128),
+            Some(SortBy::Mtime) => match entry.metadata().and_then(|m| m.modified()) {
+                Ok(time) => match time.duration_since(UNIX_EPOCH) {
+                    Ok(since) => since.as_nanos() as i128,
+                    Err(before) => -(before.duration().as_nanos() as i128),
+                },
+                Err(_) => i128::MIN,
+            },
+            Some(SortBy::Name) | None => 0,
+        };
+        let components: Vec<_> = entry.path().components().collect();
+        let names = components[components.len().saturating_sub(entry.depth())..]
+            .iter()
+            .map(|component| component.as_os_str().to_owned())
+            .collect();
+        SortKey {
+            field: Reverse(field),
+            start,
+            names,
+        }
+    }
+}
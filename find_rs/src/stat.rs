@@ -0,0 +1,339 @@
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone};
+use std::fs::{FileType, Metadata};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A numeric argument: `+N` is more than N, `-N` less than N, `N` exactly N.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Less(i64),
+    Exactly(i64),
+    Greater(i64),
+}
+
+impl Comparison {
+    pub fn parse(arg: &str) -> Option<Comparison> {
+        let (make, digits): (fn(i64) -> Comparison, &str) = match arg.as_bytes().first() {
+            Some(b'+') => (Comparison::Greater, &arg[1..]),
+            Some(b'-') => (Comparison::Less, &arg[1..]),
+            _ => (Comparison::Exactly, arg),
+        };
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        digits.parse().ok().map(make)
+    }
+
+    pub fn matches(self, value: i64) -> bool {
+        match self {
+            Comparison::Less(n) => value < n,
+            Comparison::Exactly(n) => value == n,
+            Comparison::Greater(n) => value > n,
+        }
+    }
+}
+
+/// Parses the argument of `-size`: a comparison followed by an optional unit,
+/// `b` (512-byte blocks, the default), `c` (bytes), `w` (2-byte words), `k`,
+/// `M` or `G`. Returns the comparison and the unit size in bytes.
+pub fn parse_size(arg: &str) -> Option<(Comparison, u64)> {
+    let (number, unit) = match arg.char_indices().last() {
+        Some((i, 'b')) => (&arg[..i], 512),
+        Some((i, 'c')) => (&arg[..i], 1),
+        Some((i, 'w')) => (&arg[..i], 2),
+        Some((i, 'k')) => (&arg[..i], 1 << 10),
+        Some((i, 'M')) => (&arg[..i], 1 << 20),
+        Some((i, 'G')) => (&arg[..i], 1 << 30),
+        _ => (arg, 512),
+    };
+    Comparison::parse(number).map(|comparison| (comparison, unit))
+}
+
+/// The size of a file in `unit`s, rounded up as GNU find does: with `-size
+/// -1M` only empty files match, since any other file counts as at least 1M.
+pub fn size_in_units(len: u64, unit: u64) -> i64 {
+    len.div_ceil(unit) as i64
+}
+
+/// Which of a file's timestamps a predicate looks at.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeField {
+    /// Last access (`a`)
+    Access,
+    /// Creation (`B`), where the platform records it
+    Birth,
+    /// Last status change (`c`)
+    Change,
+    /// Last modification (`m`)
+    Modify,
+}
+
+impl TimeField {
+    /// The field for a letter of `-newerXY`.
+    pub fn from_letter(letter: char) -> Option<TimeField> {
+        match letter {
+            'a' => Some(TimeField::Access),
+            'B' => Some(TimeField::Birth),
+            'c' => Some(TimeField::Change),
+            'm' => Some(TimeField::Modify),
+            _ => None,
+        }
+    }
+
+    pub fn of(self, metadata: &Metadata) -> Option<SystemTime> {
+        match self {
+            TimeField::Access => metadata.accessed().ok(),
+            TimeField::Birth => metadata.created().ok(),
+            TimeField::Modify => metadata.modified().ok(),
+            TimeField::Change => change_time(metadata),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn change_time(metadata: &Metadata) -> Option<SystemTime> {
+    use std::os::unix::fs::MetadataExt;
+    let nanos = Duration::from_nanos(metadata.ctime_nsec() as u64);
+    match u64::try_from(metadata.ctime()) {
+        Ok(secs) => Some(UNIX_EPOCH + Duration::from_secs(secs) + nanos),
+        Err(_) => UNIX_EPOCH.checked_sub(Duration::from_secs(metadata.ctime().unsigned_abs())),
+    }
+}
+
+#[cfg(not(unix))]
+fn change_time(metadata: &Metadata) -> Option<SystemTime> {
+    metadata.modified().ok()
+}
+
+/// The letter `-type` and `%y` use for a file type: `f`, `d`, `l`, `b`
+/// (block device), `c` (character device), `p` (FIFO), `s` (socket), or `U`.
+pub fn type_char(file_type: FileType) -> char {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+        if file_type.is_block_device() {
+            return 'b';
+        } else if file_type.is_char_device() {
+            return 'c';
+        } else if file_type.is_fifo() {
+            return 'p';
+        } else if file_type.is_socket() {
+            return 's';
+        }
+    }
+    if file_type.is_file() {
+        'f'
+    } else if file_type.is_dir() {
+        'd'
+    } else if file_type.is_symlink() {
+        'l'
+    } else {
+        'U'
+    }
+}
+
+/// The owner and group of a file, where the platform records them.
+#[cfg(unix)]
+pub fn owner(metadata: &Metadata) -> Option<(u32, u32)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.uid(), metadata.gid()))
+}
+
+#[cfg(not(unix))]
+pub fn owner(_metadata: &Metadata) -> Option<(u32, u32)> {
+    None
+}
+
+/// The device and inode number of a file, which together identify it.
+#[cfg(unix)]
+pub fn inode(metadata: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+pub fn inode(_metadata: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// The number of hard links to a file.
+#[cfg(unix)]
+pub fn links(metadata: &Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.nlink())
+}
+
+#[cfg(not(unix))]
+pub fn links(_metadata: &Metadata) -> Option<u64> {
+    None
+}
+
+/// The space a file takes on disk, in 1024-byte blocks rounded up.
+#[cfg(unix)]
+pub fn blocks(metadata: &Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.blocks().div_ceil(2)
+}
+
+#[cfg(not(unix))]
+pub fn blocks(metadata: &Metadata) -> u64 {
+    metadata.len().div_ceil(1024)
+}
+
+/// The major and minor numbers of a block or character device.
+#[cfg(unix)]
+pub fn device(metadata: &Metadata) -> Option<(u32, u32)> {
+    use std::os::unix::fs::{FileTypeExt, MetadataExt};
+    let file_type = metadata.file_type();
+    if !(file_type.is_block_device() || file_type.is_char_device()) {
+        return None;
+    }
+    let rdev = metadata.rdev();
+    Some((libc::major(rdev) as u32, libc::minor(rdev) as u32))
+}
+
+#[cfg(not(unix))]
+pub fn device(_metadata: &Metadata) -> Option<(u32, u32)> {
+    None
+}
+
+/// The permission bits of a file; elsewhere than on unix they are derived
+/// from the read-only flag.
+#[cfg(unix)]
+pub fn mode(metadata: &Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o7777
+}
+
+#[cfg(not(unix))]
+pub fn mode(metadata: &Metadata) -> u32 {
+    match metadata.permissions().readonly() {
+        true => 0o555,
+        false => 0o777,
+    }
+}
+
+/// Whether the age of `time`, relative to `now` and in `unit`-second periods,
+/// satisfies `comparison` with GNU find's rounding.
+///
+/// Days are counted whole, so a file modified 47 hours ago is 1 day old and
+/// `-mtime +1` needs at least 48 hours. Minutes are compared exactly for `-N`
+/// and `+N`, so `-mmin -1` means less than 60 seconds; only `N` rounds up, so
+/// a file modified 10 seconds ago is 1 minute old and `-mmin 0` matches
+/// nothing in the past.
+pub fn age_matches(comparison: Comparison, now: SystemTime, time: SystemTime, unit: u64) -> bool {
+    let secs = match now.duration_since(time) {
+        Ok(age) => age.as_secs_f64(),
+        Err(e) => -e.duration().as_secs_f64(),
+    };
+    let units = secs / unit as f64;
+    if unit >= 86400 {
+        return comparison.matches(units.floor() as i64);
+    }
+    match comparison {
+        Comparison::Less(n) => units < n as f64,
+        Comparison::Exactly(n) => units.ceil() as i64 == n,
+        Comparison::Greater(n) => units > n as f64,
+    }
+}
+
+/// Parses the reference time of `-newerXt`: `@SECONDS` since the epoch, an
+/// RFC 3339 timestamp, or `YYYY-MM-DD` optionally followed by ` HH:MM[:SS]`
+/// or `THH:MM[:SS]` in local time.
+pub fn parse_time(arg: &str) -> Option<SystemTime> {
+    if let Some(secs) = arg.strip_prefix('@') {
+        let secs: f64 = secs.parse().ok()?;
+        return if secs >= 0.0 {
+            UNIX_EPOCH.checked_add(Duration::from_secs_f64(secs))
+        } else {
+            UNIX_EPOCH.checked_sub(Duration::from_secs_f64(-secs))
+        };
+    }
+
+    if let Ok(time) = DateTime::parse_from_rfc3339(arg) {
+        return Some(time.into());
+    }
+
+    let formats = [
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+    ];
+    let naive = formats
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(arg, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(arg, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })?;
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(SystemTime::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{age_matches, parse_size, size_in_units, Comparison};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_comparison() {
+        assert_eq!(Comparison::parse("+3"), Some(Comparison::Greater(3)));
+        assert_eq!(Comparison::parse("-3"), Some(Comparison::Less(3)));
+        assert_eq!(Comparison::parse("3"), Some(Comparison::Exactly(3)));
+        assert_eq!(Comparison::parse("+"), None);
+        assert_eq!(Comparison::parse("3x"), None);
+        assert_eq!(Comparison::parse("+-3"), None);
+    }
+
+    #[test]
+    fn test_size_rounds_up() {
+        assert_eq!(parse_size("-1M"), Some((Comparison::Less(1), 1 << 20)));
+        assert_eq!(parse_size("10"), Some((Comparison::Exactly(10), 512)));
+        assert_eq!(size_in_units(0, 1 << 20), 0);
+        assert_eq!(size_in_units(1, 1 << 20), 1);
+        assert_eq!(size_in_units(513, 512), 2);
+    }
+
+    #[test]
+    fn test_age_truncates() {
+        let now = UNIX_EPOCH + Duration::from_secs(10 * 86400);
+        let days = |comparison, hours: u64| {
+            age_matches(
+                comparison,
+                now,
+                now - Duration::from_secs(hours * 3600),
+                86400,
+            )
+        };
+        assert!(days(Comparison::Exactly(0), 23));
+        assert!(days(Comparison::Exactly(1), 47));
+        assert!(days(Comparison::Less(2), 47));
+        assert!(!days(Comparison::Greater(1), 47));
+        assert!(days(Comparison::Greater(1), 48));
+        let future = now + Duration::from_secs(1);
+        assert!(age_matches(Comparison::Less(0), now, future, 86400));
+
+        // Only an exact number of minutes rounds the age up.
+        let minutes = |comparison, secs: u64| {
+            age_matches(comparison, now, now - Duration::from_secs(secs), 60)
+        };
+        for (secs, rounded) in [(0, 0), (10, 1), (60, 1), (70, 2), (3590, 60), (3610, 61)] {
+            assert!(minutes(Comparison::Exactly(rounded), secs), "{}", secs);
+        }
+        assert!(minutes(Comparison::Less(1), 10));
+        assert!(minutes(Comparison::Less(1), 50));
+        assert!(!minutes(Comparison::Less(1), 60));
+        for secs in [60, 70, 110] {
+            assert!(minutes(Comparison::Less(2), secs), "{}", secs);
+        }
+        assert!(!minutes(Comparison::Less(2), 120));
+        assert!(minutes(Comparison::Less(60), 3590));
+        assert!(!minutes(Comparison::Greater(1), 60));
+        assert!(minutes(Comparison::Greater(1), 70));
+        assert!(!minutes(Comparison::Greater(60), 3590));
+        assert!(minutes(Comparison::Greater(60), 3610));
+    }
+}
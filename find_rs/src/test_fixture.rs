@@ -0,0 +1,26 @@
+//! The tree that the unit tests of predicates and actions evaluate entries of.
+
+use crate::entry::Entry;
+use std::fs;
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+/// The entry for `path`, at depth 1 and without following links.
+pub fn entry(path: impl Into<PathBuf>) -> Entry {
+    Entry::new(path.into(), 1, false).unwrap()
+}
+
+/// A temporary directory with `file` ("x"), `notes.txt` (two lines),
+/// `data.bin` (1000 zero bytes), the empty file `empty`, `dir` holding
+/// `inner` ("x"), and the empty directory `hollow`.
+pub fn fixture() -> TempDir {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("file"), "x").unwrap();
+    fs::write(tmp.path().join("notes.txt"), "alpha\nbeta\n").unwrap();
+    fs::write(tmp.path().join("data.bin"), vec![0u8; 1000]).unwrap();
+    fs::write(tmp.path().join("empty"), "").unwrap();
+    fs::create_dir(tmp.path().join("dir")).unwrap();
+    fs::write(tmp.path().join("dir/inner"), "x").unwrap();
+    fs::create_dir(tmp.path().join("hollow")).unwrap();
+    tmp
+}
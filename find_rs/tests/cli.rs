@@ -3,7 +3,13 @@ use assert_cmd::Command;
 use predicates::prelude::*;
 use pretty_assertions::assert_eq;
 use rand::{distributions::Alphanumeric, Rng};
-use std::{borrow::Cow, fs, path::Path};
+use std::{
+    borrow::Cow,
+    fs::{self, File},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use tempfile::TempDir;
 
 const PRG: &str = "find_rs";
 
@@ -28,28 +34,53 @@ fn skips_bad_dir() -> Result<()> {
     let bad = gen_bad_file();
     let expected = format!("{}: .* [(]os error [23][)]", &bad);
     Command::cargo_bin(PRG)?
-        .arg(&bad)
+        .args([&bad, "tests/inputs", "-maxdepth", "0"])
         .assert()
-        .success()
+        .failure()
+        .stdout("tests/inputs\n")
         .stderr(predicate::str::is_match(expected)?);
     Ok(())
 }
 
 // --------------------------------------------------
 #[test]
-fn dies_bad_name() -> Result<()> {
+fn empty_result_prints_nothing() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-name", "no-such-name"])
+        .assert()
+        .success()
+        .stdout("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_regex() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--regex", "["])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid regular expression '['"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_regextype() -> Result<()> {
     Command::cargo_bin(PRG)?
-        .args(["--name", "*.csv"])
+        .args(["--regextype", "perl", "--regex", "x"])
         .assert()
         .failure()
-        .stderr(predicate::str::contains("error: invalid value '*.csv'"));
+        .stderr(predicate::str::contains(
+            "Unknown regular expression type 'perl'",
+        ));
     Ok(())
 }
 
 // --------------------------------------------------
 #[test]
 fn dies_bad_type() -> Result<()> {
-    let expected = "error: invalid value 'x' for '--type <TYPE>'";
+    let expected = "Unknown argument to -type: x";
     Command::cargo_bin(PRG)?
         .args(["--type", "x"])
         .assert()
@@ -60,14 +91,14 @@ fn dies_bad_type() -> Result<()> {
 
 // --------------------------------------------------
 #[cfg(windows)]
-fn format_file_name(expected_file: &str) -> Cow<str> {
+fn format_file_name(expected_file: &str) -> Cow<'_, str> {
     // Equivalent to: Cow::Owned(format!("{}.windows", expected_file))
     format!("{}.windows", expected_file).into()
 }
 
 // --------------------------------------------------
 #[cfg(not(windows))]
-fn format_file_name(expected_file: &str) -> Cow<str> {
+fn format_file_name(expected_file: &str) -> Cow<'_, str> {
     // Equivalent to: Cow::Borrowed(expected_file)
     expected_file.into()
 }
@@ -77,19 +108,131 @@ fn run(args: &[&str], expected_file: &str) -> Result<()> {
     let file = format_file_name(expected_file);
     let contents = fs::read_to_string(file.as_ref())?;
     let mut expected: Vec<&str> = contents.split('\n').filter(|s| !s.is_empty()).collect();
-    expected.sort();
+    let ordered = is_ordered(args);
+    if !ordered {
+        expected.sort();
+    }
 
     let cmd = Command::cargo_bin(PRG)?.args(args).assert().success();
     let out = cmd.get_output();
     let stdout = String::from_utf8(out.stdout.clone())?;
     let mut lines: Vec<&str> = stdout.split('\n').filter(|s| !s.is_empty()).collect();
-    lines.sort();
+    if !ordered {
+        lines.sort();
+    }
 
     assert_eq!(lines, expected);
 
     Ok(())
 }
 
+// --------------------------------------------------
+/// A tree built in a temporary directory, for what the static trees cannot
+/// hold: modification times, modes, links, empty directories and `.git`.
+struct Fixture(TempDir);
+
+impl Fixture {
+    fn new() -> Result<Fixture> {
+        Ok(Fixture(tempfile::tempdir()?))
+    }
+
+    /// Starts from a copy of a tree in `tests/fixtures`, for tests that change it.
+    fn copy(tree: &str) -> Result<Fixture> {
+        let fixture = Fixture::new()?;
+        let mut dirs = vec![Path::new(tree).to_path_buf()];
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(&dir)? {
+                let path = entry?.path();
+                let target = fixture.path().join(path.strip_prefix(tree)?);
+                if path.is_dir() {
+                    fs::create_dir(target)?;
+                    dirs.push(path);
+                } else {
+                    fs::copy(path, target)?;
+                }
+            }
+        }
+        Ok(fixture)
+    }
+
+    fn path(&self) -> &Path {
+        self.0.path()
+    }
+
+    fn join(&self, path: &str) -> PathBuf {
+        self.path().join(path)
+    }
+
+    fn dir(self, path: &str) -> Result<Fixture> {
+        fs::create_dir_all(self.join(path))?;
+        Ok(self)
+    }
+
+    fn file(self, path: &str, contents: impl AsRef<[u8]>) -> Result<Fixture> {
+        fs::write(self.join(path), contents)?;
+        Ok(self)
+    }
+
+    /// Sets the modification time to `secs` seconds ago.
+    fn age(self, path: &str, secs: u64) -> Result<Fixture> {
+        File::options()
+            .write(true)
+            .open(self.join(path))?
+            .set_modified(SystemTime::now() - Duration::from_secs(secs))?;
+        Ok(self)
+    }
+
+    #[cfg(unix)]
+    fn mode(self, path: &str, mode: u32) -> Result<Fixture> {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(self.join(path), fs::Permissions::from_mode(mode))?;
+        Ok(self)
+    }
+
+    #[cfg(unix)]
+    fn link(self, target: &str, path: &str) -> Result<Fixture> {
+        std::os::unix::fs::symlink(target, self.join(path))?;
+        Ok(self)
+    }
+
+    #[cfg(unix)]
+    fn hard_link(self, original: &str, path: &str) -> Result<Fixture> {
+        fs::hard_link(self.join(original), self.join(path))?;
+        Ok(self)
+    }
+}
+
+// --------------------------------------------------
+/// Runs in `dir`, which is also the default starting point, and returns the
+/// printed lines without the leading `./`. They are sorted unless the
+/// arguments ask for an order of their own.
+fn find_in(dir: impl AsRef<Path>, args: &[&str]) -> Result<Vec<String>> {
+    let cmd = Command::cargo_bin(PRG)?
+        .current_dir(dir)
+        .args(args)
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let prefix = format!(".{}", std::path::MAIN_SEPARATOR);
+    let mut lines: Vec<String> = stdout
+        .lines()
+        .map(|line| line.strip_prefix(&prefix).unwrap_or(line).into())
+        .collect();
+    if !is_ordered(args) {
+        lines.sort();
+    }
+    Ok(lines)
+}
+
+// --------------------------------------------------
+/// Whether the arguments fix the order of the output.
+fn is_ordered(args: &[&str]) -> bool {
+    args.iter().any(|arg| {
+        let name = arg.trim_start_matches('-');
+        name.starts_with("sort") || name.starts_with("duplicates")
+    })
+}
+
 // --------------------------------------------------
 #[test]
 fn path1() -> Result<()> {
@@ -217,7 +360,7 @@ fn type_l() -> Result<()> {
 #[test]
 fn type_f_l() -> Result<()> {
     run(
-        &["tests/inputs", "-t", "link", "-t", "file"],
+        &["tests/inputs", "-t", "link", "-o", "-t", "file"],
         "tests/expected/type_f_l.txt",
     )
 }
@@ -226,7 +369,7 @@ fn type_f_l() -> Result<()> {
 #[test]
 fn name_csv() -> Result<()> {
     run(
-        &["tests/inputs", "-n", ".*[.]csv"],
+        &["tests/inputs", "-n", "*.csv"],
         "tests/expected/name_csv.txt",
     )
 }
@@ -235,7 +378,7 @@ fn name_csv() -> Result<()> {
 #[test]
 fn name_csv_mp3() -> Result<()> {
     run(
-        &["tests/inputs", "-n", ".*[.]csv", "-n", ".*[.]mp3"],
+        &["tests/inputs", "-n", "*.csv", "-o", "-n", "*.mp3"],
         "tests/expected/name_csv_mp3.txt",
     )
 }
@@ -244,7 +387,7 @@ fn name_csv_mp3() -> Result<()> {
 #[test]
 fn name_txt_path_a_d() -> Result<()> {
     run(
-        &["tests/inputs/a", "tests/inputs/d", "--name", ".*.txt"],
+        &["tests/inputs/a", "tests/inputs/d", "--name", "*.txt"],
         "tests/expected/name_txt_path_a_d.txt",
     )
 }
@@ -259,7 +402,7 @@ fn name_a() -> Result<()> {
 #[test]
 fn type_f_name_a() -> Result<()> {
     run(
-        &["tests/inputs", "-t", "file", "-n", "a"],
+        &["tests/inputs", "-t", "file", "-n", "a.*"],
         "tests/expected/type_f_name_a.txt",
     )
 }
@@ -275,35 +418,1420 @@ fn type_d_name_a() -> Result<()> {
 
 // --------------------------------------------------
 #[test]
-fn path_g() -> Result<()> {
-    run(&["tests/inputs/g.csv"], "tests/expected/path_g.txt")
+fn name_is_not_substring() -> Result<()> {
+    run(&["tests/inputs", "-n", "csv"], "tests/expected/empty.txt")
+}
+
+// --------------------------------------------------
+#[test]
+fn name_bracket() -> Result<()> {
+    run(
+        &["tests/inputs", "-t", "file", "-n", "[!bc].*"],
+        "tests/expected/name_bracket.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn iname() -> Result<()> {
+    run(
+        &["tests/inputs", "--iname", "A*"],
+        "tests/expected/iname_a.txt",
+    )
 }
 
 // --------------------------------------------------
 #[test]
 #[cfg(not(windows))]
-fn unreadable_dir() -> Result<()> {
-    let dirname = "tests/inputs/cant-touch-this";
-    if !Path::new(dirname).exists() {
-        fs::create_dir(dirname)?;
-    }
+fn path_pattern() -> Result<()> {
+    run(
+        &["tests/inputs", "--path", "*/b/*"],
+        "tests/expected/path_pattern_b.txt",
+    )
+}
 
-    std::process::Command::new("chmod")
-        .args(["000", dirname])
-        .status()
-        .expect("failed");
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn ipath_pattern() -> Result<()> {
+    run(
+        &["tests/inputs", "--ipath", "*/B/*", "-t", "file"],
+        "tests/expected/ipath_pattern_b.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn regex_emacs() -> Result<()> {
+    run(
+        &["tests/inputs", "--regex", r".*/[a-c]\.\(csv\|mp3\)"],
+        "tests/expected/regex_csv_mp3.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn regex_posix_extended() -> Result<()> {
+    run(
+        &[
+            "tests/inputs",
+            "--regextype",
+            "posix-extended",
+            "--regex",
+            r".*/[a-c]\.(csv|mp3)",
+        ],
+        "tests/expected/regex_csv_mp3.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn regex_posix_basic() -> Result<()> {
+    run(
+        &[
+            "tests/inputs",
+            "--regextype",
+            "posix-basic",
+            "--regex",
+            r".*/d\{1,2\}\..*",
+        ],
+        "tests/expected/regex_d.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn regex_is_anchored() -> Result<()> {
+    run(
+        &["tests/inputs", "--regex", "e.mp3"],
+        "tests/expected/empty.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn iregex() -> Result<()> {
+    run(
+        &["tests/inputs", "--iregex", ".*/D\\.T.*"],
+        "tests/expected/regex_d.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn or_binds_looser_than_and() -> Result<()> {
+    // ( -type d -a -name b ) -o ( -name *.txt -a -print ): b itself is not printed
+    run(
+        &[
+            "tests/inputs",
+            "-type",
+            "d",
+            "-name",
+            "b",
+            "-o",
+            "-name",
+            "*.txt",
+            "-print",
+        ],
+        "tests/expected/or_precedence.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn parens_not_path() -> Result<()> {
+    run(
+        &[
+            "tests/inputs",
+            "(",
+            "-name",
+            "*.csv",
+            "-o",
+            "-name",
+            "*.mp3",
+            ")",
+            "!",
+            "-path",
+            "*/a/*",
+        ],
+        "tests/expected/parens_not_path.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn not_type() -> Result<()> {
+    run(
+        &[
+            "tests/inputs",
+            "-not",
+            "-type",
+            "d",
+            "-and",
+            "-name",
+            "*.t*",
+        ],
+        "tests/expected/not_type_d_name_t.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn comma() -> Result<()> {
+    run(
+        &[
+            "tests/inputs",
+            "-name",
+            "a",
+            "-print",
+            ",",
+            "-name",
+            "d",
+            "-print",
+        ],
+        "tests/expected/comma_a_d.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn false_prints_nothing() -> Result<()> {
+    run(&["tests/inputs", "-false"], "tests/expected/empty.txt")
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_missing_paren() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "(", "-name", "a"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("expecting to find a ')'"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_extra_paren() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-name", "a", ")"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("too many ')'"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_missing_argument() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-name"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("missing argument to '-name'"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_unknown_predicate() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-foo"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown predicate '-foo'"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn maxdepth() -> Result<()> {
+    run(
+        &["tests/inputs", "-maxdepth", "1"],
+        "tests/expected/maxdepth_1.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn mindepth() -> Result<()> {
+    run(
+        &["tests/inputs", "-mindepth", "3"],
+        "tests/expected/mindepth_3.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn mindepth_maxdepth_type() -> Result<()> {
+    run(
+        &[
+            "tests/inputs",
+            "-mindepth",
+            "1",
+            "-maxdepth",
+            "1",
+            "-type",
+            "d",
+        ],
+        "tests/expected/depth_1_dirs.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn prune() -> Result<()> {
+    run(
+        &["tests/inputs", "-name", "d", "-prune", "-o", "-print"],
+        "tests/expected/prune_d.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn prune_prints_pruned_directory() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-name", "d", "-prune"])
+        .assert()
+        .success()
+        .stdout("tests/inputs/d\n");
+    Ok(())
+}
 
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn depth_first() -> Result<()> {
     let cmd = Command::cargo_bin(PRG)?
-        .arg("tests/inputs")
+        .args(["tests/inputs/a", "-depth"])
         .assert()
         .success();
-    fs::remove_dir(dirname)?;
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let lines: Vec<&str> = stdout.lines().collect();
 
-    let out = cmd.get_output();
-    let stdout = String::from_utf8(out.stdout.clone())?;
-    let lines: Vec<&str> = stdout.split('\n').filter(|s| !s.is_empty()).collect();
+    // Every directory comes after everything below it.
+    assert_eq!(lines.len(), 6);
+    assert_eq!(lines.last(), Some(&"tests/inputs/a"));
+    for (i, dir) in lines.iter().enumerate() {
+        assert!(lines[i + 1..]
+            .iter()
+            .all(|later| !later.starts_with(&format!("{dir}/"))));
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_maxdepth() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-maxdepth", "x"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Expected a positive decimal integer argument to -maxdepth, but got 'x'",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+/// Files of known sizes and modification times: `empty` (0 bytes), `small`
+/// (1500 bytes), `big` (2 MiB, 3 days old), `day` (30 hours old), `recent`
+/// (10 minutes old) and the empty directory `dir`.
+fn stat_fixture() -> Result<Fixture> {
+    Fixture::new()?
+        .file("empty", "")?
+        .file("small", [b'x'; 1500])?
+        .file("big", vec![b'x'; 2 << 20])?
+        .file("day", "")?
+        .file("recent", "")?
+        .dir("dir")?
+        .age("big", 3 * 86400)?
+        .age("day", 30 * 3600)?
+        .age("recent", 10 * 60)
+}
+
+// --------------------------------------------------
+#[test]
+fn size() -> Result<()> {
+    let tmp = stat_fixture()?;
+    assert_eq!(find_in(tmp.path(), &["-size", "+1M"])?, ["big"]);
+    assert_eq!(find_in(tmp.path(), &["-size", "1500c"])?, ["small"]);
+    // 1500 bytes are 3 blocks of 512 bytes when rounded up.
+    assert_eq!(find_in(tmp.path(), &["-size", "3"])?, ["small"]);
+    assert_eq!(find_in(tmp.path(), &["-size", "2k"])?, ["small"]);
+    // Only empty files are smaller than 1M, since sizes are rounded up.
+    assert_eq!(
+        find_in(tmp.path(), &["-type", "f", "-size", "-1M"])?,
+        ["day", "empty", "recent"]
+    );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn empty() -> Result<()> {
+    let tmp = stat_fixture()?;
+    assert_eq!(
+        find_in(tmp.path(), &["-empty"])?,
+        ["day", "dir", "empty", "recent"]
+    );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn mtime() -> Result<()> {
+    let tmp = stat_fixture()?;
+    assert_eq!(find_in(tmp.path(), &["-mtime", "+1"])?, ["big"]);
+    // 30 hours is 1 day when counting whole days.
+    assert_eq!(find_in(tmp.path(), &["-mtime", "1"])?, ["day"]);
+    assert_eq!(
+        find_in(tmp.path(), &["-type", "f", "-mtime", "-1"])?,
+        ["empty", "recent", "small"]
+    );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn mmin() -> Result<()> {
+    let mut tmp = stat_fixture()?;
+    assert_eq!(
+        find_in(tmp.path(), &["-mmin", "+5", "-mmin", "-20"])?,
+        ["recent"]
+    );
+
+    // As in GNU find, ages are rounded up to whole minutes for N, but -N and
+    // +N compare the exact age.
+    for secs in [10, 50, 70, 110, 3590, 3610] {
+        let name = format!("age{}", secs);
+        tmp = tmp.file(&name, "")?.age(&name, secs)?;
+    }
+    let ages = |mmin| find_in(tmp.path(), &["-name", "age*", "-mmin", mmin]);
+    assert_eq!(ages("1")?, ["age10", "age50"]);
+    assert_eq!(ages("2")?, ["age110", "age70"]);
+    assert_eq!(ages("+1")?, ["age110", "age3590", "age3610", "age70"]);
+    assert_eq!(ages("+2")?, ["age3590", "age3610"]);
+    assert_eq!(ages("60")?, ["age3590"]);
+    assert_eq!(ages("0")?, Vec::<String>::new());
+    assert_eq!(ages("-1")?, ["age10", "age50"]);
+    assert_eq!(ages("-2")?, ["age10", "age110", "age50", "age70"]);
+    assert_eq!(
+        ages("-60")?,
+        ["age10", "age110", "age3590", "age50", "age70"]
+    );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn newer() -> Result<()> {
+    let tmp = stat_fixture()?;
+    assert_eq!(
+        find_in(tmp.path(), &["-type", "f", "-newer", "day"])?,
+        ["empty", "recent", "small"]
+    );
+    Ok(())
+}
 
-    assert_eq!(lines.len(), 17);
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn newer_link_reference() -> Result<()> {
+    // The link itself is new; what it leads to is older than `mid`.
+    let tmp = Fixture::new()?
+        .file("old", "")?
+        .file("mid", "")?
+        .age("old", 4 * 365 * 86400)?
+        .age("mid", 2 * 365 * 86400)?
+        .link("old", "ref")?;
+    let newer = |follow| find_in(tmp.path(), &[follow, ".", "-type", "f", "-newer", "ref"]);
+    assert_eq!(newer("-P")?, Vec::<String>::new());
+    assert_eq!(newer("-H")?, ["mid"]);
+    assert_eq!(newer("-L")?, ["mid"]);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn newermt() -> Result<()> {
+    let tmp = stat_fixture()?;
+    assert_eq!(
+        find_in(
+            tmp.path(),
+            &["-type", "f", "!", "-newermt", "@0", "-o", "-name", "big"]
+        )?,
+        ["big"]
+    );
+    assert_eq!(
+        find_in(tmp.path(), &["-newermt", "2100-01-01 00:00:00"])?,
+        Vec::<String>::new()
+    );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_size() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-size", "1Q"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid argument '1Q' to -size"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_newer_reference() -> Result<()> {
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-newer", &bad])
+        .assert()
+        .failure()
+        .stderr(predicate::str::starts_with(format!("find_rs: {bad}: ")));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_newermt() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-newermt", "yesterday"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("interpret 'yesterday'"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn path_g() -> Result<()> {
+    run(&["tests/inputs/g.csv"], "tests/expected/path_g.txt")
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn printf() -> Result<()> {
+    run(
+        &[
+            "tests/inputs",
+            "-type",
+            "d",
+            "-printf",
+            "%d %y %h %f %P\\n",
+            "-o",
+            "-printf",
+            "%d %y %s %h %f %P\\n",
+        ],
+        "tests/expected/printf.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn printf_width_and_escapes() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-maxdepth", "0"])
+        .args(["-printf", "[%-8f|%4d|%.3p]%%\\t\\101\\c ignored"])
+        .assert()
+        .success()
+        .stdout("[inputs  |   0|tes]%\tA");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(target_os = "linux")]
+fn printf_raw_bytes() -> Result<()> {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    let tmp = Fixture::new()?;
+    File::create(tmp.path().join(OsStr::from_bytes(b"caf\xe9")))?;
+    let cmd = Command::cargo_bin(PRG)?
+        .arg(tmp.path())
+        .args(["-mindepth", "1", "-printf", "%f|%P\\377\\n"])
+        .assert()
+        .success();
+    assert_eq!(cmd.get_output().stdout, b"caf\xe9|caf\xe9\xff\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn print0() -> Result<()> {
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-name", "*.mp3", "-print0"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    assert!(stdout.ends_with('\0'));
+    let mut paths: Vec<&str> = stdout.trim_end_matches('\0').split('\0').collect();
+    paths.sort();
+    assert_eq!(paths.len(), 2);
+    assert!(paths.iter().all(|path| path.ends_with(".mp3")));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn fprint() -> Result<()> {
+    let tmp = Fixture::new()?;
+    let out = tmp.join("out.txt");
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-name", "*.csv", "-fprint"])
+        .arg(&out)
+        .assert()
+        .success()
+        .stdout("");
+    let contents = fs::read_to_string(&out)?;
+    let mut lines: Vec<&str> = contents.lines().collect();
+    lines.sort();
+    let expected = fs::read_to_string(format_file_name("tests/expected/name_csv.txt").as_ref())?;
+    let mut expected: Vec<&str> = expected.lines().collect();
+    expected.sort();
+    assert_eq!(lines, expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_printf_trailing_percent() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-printf", "%"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("% at end of format string"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn exec() -> Result<()> {
+    run(
+        &[
+            "tests/inputs",
+            "-name",
+            "*.csv",
+            "-exec",
+            "echo",
+            "found",
+            "{}",
+            ";",
+        ],
+        "tests/expected/exec_csv.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn exec_status() -> Result<()> {
+    run(
+        &[
+            "tests/inputs",
+            "-name",
+            "*.csv",
+            "-exec",
+            "test",
+            "-h",
+            "{}",
+            ";",
+            "-print",
+        ],
+        "tests/expected/exec_status.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn execdir_batch() -> Result<()> {
+    run(
+        &["tests/inputs", "-type", "f", "-execdir", "echo", "{}", "+"],
+        "tests/expected/execdir_batch.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn exec_batch() -> Result<()> {
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-type", "f", "-exec", "echo", "{}", "+"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    // All of the paths fit on a single command line.
+    assert_eq!(stdout.lines().count(), 1);
+    assert_eq!(stdout.split_whitespace().count(), 8);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn exec_batch_failure() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-exec", "false", "{}", "+"])
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn ok() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args([
+            "tests/inputs",
+            "-name",
+            "*.mp3",
+            "-ok",
+            "echo",
+            "yes",
+            "{}",
+            ";",
+        ])
+        .write_stdin("y\nn\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match("^yes tests/inputs/.*mp3\n$")?)
+        .stderr(predicate::str::contains("< echo yes tests/inputs/"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_exec_without_terminator() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-exec", "echo", "{}"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("missing argument to '-exec'"));
+    Ok(())
+}
+
+// --------------------------------------------------
+/// A copy of `tests/fixtures/delete`, that is `tree/a/{x.o,keep,b/y.o}` next to
+/// `outside/precious`, with the empty directory `tree/a/empty` added.
+fn delete_fixture() -> Result<Fixture> {
+    Fixture::copy("tests/fixtures/delete")?.dir("tree/a/empty")
+}
+
+// --------------------------------------------------
+#[test]
+fn delete() -> Result<()> {
+    let tmp = delete_fixture()?;
+    assert_eq!(
+        find_in(tmp.path(), &["tree", "-name", "*.o", "-delete"])?,
+        Vec::<String>::new()
+    );
+    assert!(!tmp.join("tree/a/x.o").exists());
+    assert!(!tmp.join("tree/a/b/y.o").exists());
+    assert!(tmp.join("tree/a/keep").exists());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn delete_reports_failures_and_continues() -> Result<()> {
+    let tmp = delete_fixture()?;
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["tree", "-type", "d", "-delete"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot delete"));
+    assert!(!tmp.join("tree/a/empty").exists());
+    assert!(tmp.join("tree/a/b/y.o").exists());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn delete_does_not_follow_links() -> Result<()> {
+    let tmp = delete_fixture()?.link("../outside", "tree/link")?;
+    find_in(tmp.path(), &["tree", "-mindepth", "1", "-delete"])?;
+    assert_eq!(fs::read_dir(tmp.join("tree"))?.count(), 0);
+    assert!(tmp.join("outside/precious").exists());
+
+    let tmp = delete_fixture()?
+        .link("../outside", "tree/link")?
+        .link("outside", "top")?;
+    for (follow, start) in [("-L", "tree"), ("-H", "top")] {
+        Command::cargo_bin(PRG)?
+            .current_dir(tmp.path())
+            .args([follow, start, "-mindepth", "1", "-delete"])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(
+                "-delete may not be used with -H or -L",
+            ));
+        assert!(tmp.join("outside/precious").exists());
+        assert!(tmp.join("tree/a/keep").exists());
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_delete_with_prune() -> Result<()> {
+    let tmp = delete_fixture()?;
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-name", "outside", "-prune", "-o", "-delete"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "The -delete action automatically turns on -depth",
+        ));
+    assert!(tmp.join("tree/a/keep").exists());
+    Ok(())
+}
+
+// --------------------------------------------------
+/// `tree/{vendor -> ../real, broken -> nowhere}` and `top -> tree`, next to
+/// `real/sub/f`.
+#[cfg(unix)]
+fn link_fixture() -> Result<Fixture> {
+    Fixture::new()?
+        .dir("real/sub")?
+        .dir("tree")?
+        .file("real/sub/f", "")?
+        .link("../real", "tree/vendor")?
+        .link("nowhere", "tree/broken")?
+        .link("tree", "top")
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn follow_never() -> Result<()> {
+    let tmp = link_fixture()?;
+    assert_eq!(find_in(tmp.path(), &["-P", "top"])?, ["top"]);
+    assert_eq!(
+        find_in(tmp.path(), &["tree", "-type", "l"])?,
+        ["tree/broken", "tree/vendor"]
+    );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn follow_command_line() -> Result<()> {
+    let tmp = link_fixture()?;
+    assert_eq!(
+        find_in(tmp.path(), &["-H", "top"])?,
+        ["top", "top/broken", "top/vendor"]
+    );
+    assert_eq!(find_in(tmp.path(), &["-H", "top", "-type", "d"])?, ["top"]);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn follow_always() -> Result<()> {
+    let tmp = link_fixture()?;
+    assert_eq!(
+        find_in(tmp.path(), &["-L", "tree", "-type", "f"])?,
+        ["tree/vendor/sub/f"]
+    );
+    // Only the broken link is still a link.
+    assert_eq!(
+        find_in(tmp.path(), &["-L", "top", "-type", "l"])?,
+        ["top/broken"]
+    );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn follow_reports_loop() -> Result<()> {
+    let tmp = link_fixture()?.link("..", "real/sub/up")?;
+    Command::cargo_bin(PRG)?
+        .current_dir(tmp.path())
+        .args(["-L", "tree", "-type", "f"])
+        .assert()
+        .failure()
+        .stdout("tree/vendor/sub/f\n")
+        .stderr(predicate::str::contains(
+            "find_rs: File system loop detected; 'tree/vendor/sub/up' is part of the same \
+             file system loop as 'tree/vendor'.",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn type_special_files() -> Result<()> {
+    let tmp = Fixture::new()?.file("file", "")?;
+    let status = std::process::Command::new("mkfifo")
+        .arg(tmp.join("fifo"))
+        .status()?;
+    assert!(status.success());
+    let _socket = std::os::unix::net::UnixListener::bind(tmp.join("socket"))?;
+
+    assert_eq!(find_in(tmp.path(), &["-type", "p"])?, ["fifo"]);
+    assert_eq!(find_in(tmp.path(), &["-type", "s"])?, ["socket"]);
+    assert_eq!(find_in(tmp.path(), &["-type", "p,s"])?, ["fifo", "socket"]);
+    assert_eq!(
+        find_in(tmp.path(), &["-mindepth", "1", "-type", "b,c,d"])?,
+        Vec::<String>::new()
+    );
+
+    Command::cargo_bin(PRG)?
+        .args(["/dev/null", "-type", "c"])
+        .assert()
+        .success()
+        .stdout("/dev/null\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn xtype() -> Result<()> {
+    let tmp = link_fixture()?;
+    let links = |options: &[&str], xtype| {
+        find_in(tmp.path(), &[options, &["tree", "-xtype", xtype]].concat())
+    };
+    assert_eq!(links(&[], "d")?, ["tree", "tree/vendor"]);
+    assert_eq!(links(&[], "l")?, ["tree/broken"]);
+    // With -L it is the link itself that counts.
+    assert_eq!(links(&["-L"], "l")?, ["tree/broken", "tree/vendor"]);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_duplicate_type() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-type", "f,d,f"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::starts_with(
+            "find_rs: Duplicate file type 'f' in the argument list to -type",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn samefile_inum_links() -> Result<()> {
+    use std::os::unix::fs::MetadataExt;
+    let tmp = Fixture::new()?
+        .file("original", "")?
+        .file("other", "")?
+        .hard_link("original", "hardlink")?;
+    let inum = fs::metadata(tmp.join("original"))?.ino().to_string();
+
+    let files = |args: &[&str]| find_in(tmp.path(), &[&["-type", "f"], args].concat());
+    assert_eq!(files(&["-samefile", "original"])?, ["hardlink", "original"]);
+    assert_eq!(files(&["-inum", &inum])?, ["hardlink", "original"]);
+    assert_eq!(files(&["-links", "+1"])?, ["hardlink", "original"]);
+    assert_eq!(files(&["-links", "1"])?, ["other"]);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn xdev() -> Result<()> {
+    // A tree on a single file system is walked whole.
+    run(&["tests/inputs", "-xdev"], "tests/expected/path1.txt")?;
+    run(&["tests/inputs", "-mount"], "tests/expected/path1.txt")
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(target_os = "linux")]
+fn xdev_mount_point() -> Result<()> {
+    /// Unmounts the file system even when an assertion fails.
+    struct Mount(PathBuf);
+
+    impl Drop for Mount {
+        fn drop(&mut self) {
+            let _ = std::process::Command::new("umount").arg(&self.0).status();
+        }
+    }
+
+    let tmp = Fixture::new()?.dir("mnt")?.file("top", "")?;
+    // Mounting needs privileges the test may not have, so it is skipped then.
+    let mounted = std::process::Command::new("mount")
+        .args(["-t", "tmpfs", "tmpfs"])
+        .arg(tmp.join("mnt"))
+        .stderr(std::process::Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success());
+    if !mounted {
+        return Ok(());
+    }
+    let _mount = Mount(tmp.join("mnt"));
+    fs::write(tmp.join("mnt/inside"), "")?;
+
+    // The mount point is listed but not entered.
+    assert_eq!(find_in(tmp.path(), &["-xdev"])?, [".", "mnt", "top"]);
+    assert_eq!(find_in(tmp.path(), &["-mount"])?, [".", "mnt", "top"]);
+    assert_eq!(find_in(tmp.path(), &[])?, [".", "mnt", "mnt/inside", "top"]);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_samefile_reference() -> Result<()> {
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args(["-samefile", &bad])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(format!("find_rs: {}: ", bad)));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn threads_find_the_same_entries() -> Result<()> {
+    for expr in [
+        &[][..],
+        &["-type", "f"],
+        &["-maxdepth", "1"],
+        &["-mindepth", "2", "-name", "*.csv"],
+        &["-name", "d", "-prune", "-o", "-print"],
+    ] {
+        let args = [&["tests/inputs"], expr].concat();
+        let threaded = [&args[..], &["--threads", "4"]].concat();
+        assert_eq!(find_in(".", &threaded)?, find_in(".", &args)?);
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn sorted_with_threads() -> Result<()> {
+    let expected = find_in(".", &["tests/inputs", "tests/inputs/a", "-sorted"])?;
+    assert_eq!(
+        expected[..3],
+        ["tests/inputs", "tests/inputs/a", "tests/inputs/a/a.txt"]
+    );
+    let threaded = find_in(
+        ".",
+        &["tests/inputs", "tests/inputs/a", "--sorted", "--threads=4"],
+    )?;
+    assert_eq!(threaded, expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_threads() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-threads", "0"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid argument '0' to -threads"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn respect_ignore() -> Result<()> {
+    let tmp = Fixture::new()?
+        .dir(".git/info")?
+        .dir("src")?
+        .dir("target/debug")?
+        .dir("sub/keep")?
+        .file(".hidden", "")?
+        .file("src/a.rs", "")?
+        .file("target/debug/x", "")?
+        .file("sub/keep/k.log", "")?
+        .file("sub/n.log", "")?
+        .file("note.tmp", "")?
+        .file("local.txt", "")?
+        .file(".gitignore", "target/\n")?
+        .file("sub/.gitignore", "*.log\n!keep/k.log\n")?
+        .file(".ignore", "*.tmp\n")?
+        .file(".git/info/exclude", "local.txt\n")?;
+    let files = |args: &[&str]| find_in(tmp.path(), &[&["-mindepth", "1"], args].concat());
+
+    let expected = ["src", "src/a.rs", "sub", "sub/keep", "sub/keep/k.log"];
+    assert_eq!(files(&["-respect-ignore"])?, expected);
+    assert_eq!(files(&["--respect-ignore", "--threads", "2"])?, expected);
+    assert_eq!(files(&["-respect-ignore", "-depth"])?, expected);
+    // .git is skipped even with -hidden, as fd does.
+    let with_hidden = [
+        ".gitignore",
+        ".hidden",
+        ".ignore",
+        "src",
+        "src/a.rs",
+        "sub",
+        "sub/.gitignore",
+        "sub/keep",
+        "sub/keep/k.log",
+    ];
+    for args in [
+        &["-respect-ignore", "-hidden"][..],
+        &["-respect-ignore", "-hidden", "-depth"],
+        &["-respect-ignore", "-hidden", "-threads", "2"],
+    ] {
+        assert_eq!(files(args)?, with_hidden);
+    }
+    assert_eq!(files(&["-name", "x"])?, ["target/debug/x"]);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn sort_size_mtime() -> Result<()> {
+    let tmp = stat_fixture()?;
+    let by_size = ["big", "small", "day", "empty", "recent"];
+    assert_eq!(
+        find_in(tmp.path(), &["-type", "f", "-sort", "size"])?,
+        by_size
+    );
+    assert_eq!(
+        find_in(tmp.path(), &["-type", "f", "--sort=size", "--threads", "3"])?,
+        by_size
+    );
+    assert_eq!(
+        find_in(tmp.path(), &["-type", "f", "-mmin", "+1", "-sort", "mtime"])?,
+        ["recent", "day", "big"]
+    );
+    assert_eq!(
+        find_in(tmp.path(), &["-mindepth", "1", "-sort", "name"])?,
+        ["big", "day", "dir", "empty", "recent", "small"]
+    );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_sort() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-sort", "color"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid argument 'color' to -sort",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn ls() -> Result<()> {
+    let tmp = Fixture::new()?
+        .file("f", "hello")?
+        .mode("f", 0o640)?
+        .link("f", "l")?;
+    let lines = find_in(tmp.path(), &["-mindepth", "1", "-ls", "-sorted"])?;
+    let columns = r"^ +\d+ +\d+ ";
+    let time = r"\w{3} [ \d]\d ( \d{4}|\d\d:\d\d)";
+    let file_line = format!(r"{}-rw-r----- +1 \S+ +\S+ +5 {} \./f$", columns, time);
+    let link_line = format!(r"{}lrwxrwxrwx +1 \S+ +\S+ +1 {} \./l -> f$", columns, time);
+    assert_eq!(lines.len(), 2);
+    assert!(
+        predicate::str::is_match(file_line)?.eval(&lines[0]),
+        "{}",
+        lines[0]
+    );
+    assert!(
+        predicate::str::is_match(link_line)?.eval(&lines[1]),
+        "{}",
+        lines[1]
+    );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn json() -> Result<()> {
+    let tmp = Fixture::new()?
+        .file("f\"q", "hello")?
+        .mode("f\"q", 0o640)?
+        .link("f\"q", "l")?;
+    let lines = find_in(tmp.path(), &["-mindepth", "1", "--json", "-sorted"])?;
+    assert_eq!(lines.len(), 2);
+    let prefix = r#"{"path":"./f\"q","type":"file","size":5,"mode":"0640","uid":"#;
+    assert!(lines[0].starts_with(prefix), "{}", lines[0]);
+    assert!(lines[0].contains(r#","mtime":""#));
+    assert!(lines[0].ends_with(r#","depth":1,"target":null}"#));
+    assert!(lines[1].contains(r#","type":"link","#));
+    assert!(lines[1].ends_with(r#","depth":1,"target":"f\"q"}"#));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(target_os = "linux")]
+fn json_raw_bytes() -> Result<()> {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    let tmp = Fixture::new()?;
+    // Two names that only differ where they are not UTF-8.
+    File::create(tmp.path().join(OsStr::from_bytes(b"caf\xe9")))?;
+    File::create(tmp.path().join(OsStr::from_bytes(b"caf\xe8")))?;
+    std::os::unix::fs::symlink(OsStr::from_bytes(b"caf\xe9"), tmp.join("l"))?;
+
+    let lines = find_in(tmp.path(), &["-mindepth", "1", "-json", "-sorted"])?;
+    assert_eq!(lines.len(), 3);
+    let file = |last| {
+        format!(
+            "{{\"path\":\"./caf\u{FFFD}\",\"path_bytes\":[46,47,99,97,102,{}],\"type\":\"file\",",
+            last
+        )
+    };
+    assert!(lines[0].starts_with(&file(232)), "{}", lines[0]);
+    assert!(lines[1].starts_with(&file(233)), "{}", lines[1]);
+    assert!(lines[2].starts_with(r#"{"path":"./l","type":"link","#));
+    assert!(
+        lines[2].ends_with("\"target\":\"caf\u{FFFD}\",\"target_bytes\":[99,97,102,233]}"),
+        "{}",
+        lines[2]
+    );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn contains() -> Result<()> {
+    let dir = "tests/fixtures/content";
+    run(
+        &[dir, "-contains", "^API_KEY=\\w+$"],
+        "tests/expected/contains_line.txt",
+    )?;
+    run(
+        &[dir, "-contains", "API_KEY="],
+        "tests/expected/contains_key.txt",
+    )?;
+    run(
+        &[dir, "-mime", "text/*", "-contains", "KEY"],
+        "tests/expected/contains_line.txt",
+    )?;
+    run(
+        &[dir, "-contains", "KEY", "-name", "*.png"],
+        "tests/expected/mime_png.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn mime() -> Result<()> {
+    let dir = "tests/fixtures/content";
+    run(&[dir, "-mime", "image/png"], "tests/expected/mime_png.txt")?;
+    run(
+        &[dir, "-mime", "TEXT/PLAIN"],
+        "tests/expected/mime_text.txt",
+    )?;
+    run(
+        &[dir, "-mime", "application/octet-stream"],
+        "tests/expected/mime_octet_stream.txt",
+    )?;
+    run(&[dir, "-mime", "inode/*"], "tests/expected/mime_inode.txt")
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_contains() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-contains", "("])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid regular expression '('"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn duplicates() -> Result<()> {
+    let dir = "tests/fixtures/duplicates";
+    run(&[dir, "-duplicates"], "tests/expected/duplicates.txt")?;
+    run(
+        &[dir, "--duplicates", "--threads", "3"],
+        "tests/expected/duplicates.txt",
+    )?;
+    run(
+        &[dir, "-name", "*.parquet", "-duplicates"],
+        "tests/expected/duplicates_parquet.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn duplicates_hard_links() -> Result<()> {
+    // A hard link is the same file, not a copy of it.
+    let tmp = Fixture::copy("tests/fixtures/duplicates")?
+        .hard_link("a/x.parquet", "x.parquet")?
+        .hard_link("big3", "a/big4")?;
+    let expected = ["a/big2", "big1", "", "a/x.parquet", "b/y.parquet"];
+    assert_eq!(find_in(tmp.path(), &["-duplicates"])?, expected);
+    // Nor is the file a link leads to.
+    let tmp = tmp.link("b", "c")?;
+    assert_eq!(find_in(tmp.path(), &["-L", ".", "-duplicates"])?, expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn duplicates_json() -> Result<()> {
+    run(
+        &[
+            "tests/fixtures/duplicates",
+            "-name",
+            "*.parquet",
+            "--duplicates=json",
+        ],
+        "tests/expected/duplicates_json.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_duplicates() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--duplicates=xml"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid argument 'xml' to -duplicates",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+/// Empty files with the modes 600 (`private`), 644 (`shared`), 666 (`open`)
+/// and 755 (`script`).
+#[cfg(unix)]
+fn perm_fixture() -> Result<Fixture> {
+    let mut tmp = Fixture::new()?;
+    for (name, mode) in [
+        ("private", 0o600),
+        ("shared", 0o644),
+        ("open", 0o666),
+        ("script", 0o755),
+    ] {
+        tmp = tmp.file(name, "")?.mode(name, mode)?;
+    }
+    Ok(tmp)
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn perm() -> Result<()> {
+    let tmp = perm_fixture()?;
+    let files = |args: &[&str]| find_in(tmp.path(), &[&["-type", "f"], args].concat());
+    assert_eq!(files(&["-perm", "644"])?, ["shared"]);
+    assert_eq!(files(&["-perm", "u=rw,go=r"])?, ["shared"]);
+    assert_eq!(files(&["-perm", "-o+w"])?, ["open"]);
+    assert_eq!(files(&["-perm", "-444"])?, ["open", "script", "shared"]);
+    assert_eq!(files(&["-perm", "/g=w,u=x"])?, ["open", "script"]);
+    assert_eq!(files(&["!", "-perm", "/go=r"])?, ["private"]);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn executable() -> Result<()> {
+    let tmp = perm_fixture()?;
+    assert_eq!(
+        find_in(tmp.path(), &["-type", "f", "-executable"])?,
+        ["script"]
+    );
+    assert_eq!(
+        find_in(tmp.path(), &["-type", "f", "-readable"])?,
+        ["open", "private", "script", "shared"]
+    );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn uid_gid() -> Result<()> {
+    use std::os::unix::fs::MetadataExt;
+    let tmp = perm_fixture()?;
+    let metadata = fs::metadata(tmp.join("script"))?;
+    let (uid, gid) = (metadata.uid().to_string(), metadata.gid().to_string());
+    let all = ["open", "private", "script", "shared"];
+    let files = |args: &[&str]| find_in(tmp.path(), &[&["-type", "f"], args].concat());
+    assert_eq!(files(&["-uid", &uid])?, all);
+    assert_eq!(files(&["-user", &uid, "-group", &gid])?, all);
+    assert_eq!(
+        files(&["-gid", &format!("+{}", gid)])?,
+        Vec::<String>::new()
+    );
+    assert_eq!(files(&["-nouser", "-o", "-nogroup"])?, Vec::<String>::new());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_perm() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-perm", "u+q"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid mode 'u+q'"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_unknown_user() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-user", "no-such-user-xyz"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "'no-such-user-xyz' is not the name of a known user",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn unreadable_dir() -> Result<()> {
+    let tmp = Fixture::new()?
+        .file("file", "")?
+        .dir("cant-touch-this")?
+        .mode("cant-touch-this", 0o000)?;
+    // Modes do not keep root out, so there is nothing to test then.
+    if fs::read_dir(tmp.join("cant-touch-this")).is_ok() {
+        return Ok(());
+    }
+
+    let out = Command::cargo_bin(PRG)?.current_dir(tmp.path()).output()?;
+    tmp.mode("cant-touch-this", 0o755)?;
+
+    assert!(!out.status.success());
+    let stdout = String::from_utf8(out.stdout.clone())?;
+    let mut lines: Vec<&str> = stdout.lines().collect();
+    lines.sort();
+    assert_eq!(lines, [".", "./cant-touch-this", "./file"]);
 
     let stderr = String::from_utf8(out.stderr.clone())?;
     assert!(stderr.contains("cant-touch-this: Permission denied"));
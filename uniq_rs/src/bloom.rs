@@ -0,0 +1,100 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// A fixed-size Bloom filter.
+///
+/// With `m` bits, `k` hash functions and `n` inserted items the probability of
+/// a false positive is approximately `(1 - e^(-k * n / m))^k`. The filter is
+/// sized from a memory budget and a target false-positive rate `p`, which gives
+/// `k = ceil(-log2(p))` and a capacity of `n = m * ln(2)^2 / -ln(p)` items.
+/// Inserting more than [`BloomFilter::capacity`] items is allowed, but the
+/// false-positive rate then rises above `p`.
+#[derive(Debug)]
+pub struct BloomFilter {
+    bits: Vec<u64>,
+    num_bits: u64,
+    num_hashes: u32,
+    capacity: usize,
+}
+
+impl BloomFilter {
+    /// Creates a filter using `bytes` bytes of memory and targeting `fp_rate`.
+    pub fn new(bytes: usize, fp_rate: f64) -> BloomFilter {
+        let words = (bytes / 8).max(1);
+        let num_bits = words as u64 * 64;
+        let num_hashes = (-fp_rate.log2()).ceil().max(1.0) as u32;
+        let ln2 = std::f64::consts::LN_2;
+        let capacity = (num_bits as f64 * ln2 * ln2 / -fp_rate.ln()) as usize;
+
+        BloomFilter {
+            bits: vec![0; words],
+            num_bits,
+            num_hashes,
+            capacity,
+        }
+    }
+
+    /// Number of items that can be inserted while staying within the target
+    /// false-positive rate.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Adds `item` to the set.
+    pub fn insert(&mut self, item: &[u8]) {
+        for bit in self.bit_indices(item) {
+            self.bits[(bit / 64) as usize] |= 1 << (bit % 64);
+        }
+    }
+
+    /// Returns `true` if `item` was possibly inserted, `false` if it definitely was not.
+    pub fn contains(&self, item: &[u8]) -> bool {
+        self.bit_indices(item)
+            .all(|bit| self.bits[(bit / 64) as usize] & (1 << (bit % 64)) != 0)
+    }
+
+    // Kirsch-Mitzenmacher double hashing: g_i(x) = h1(x) + i * h2(x).
+    fn bit_indices(&self, item: &[u8]) -> impl Iterator<Item = u64> {
+        let mut hasher = DefaultHasher::new();
+        item.hash(&mut hasher);
+        let h1 = hasher.finish();
+        h1.hash(&mut hasher);
+        let h2 = hasher.finish() | 1;
+
+        let num_bits = self.num_bits;
+        (0..self.num_hashes as u64).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % num_bits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BloomFilter;
+
+    #[test]
+    fn test_insert() {
+        let mut filter = BloomFilter::new(1024, 0.01);
+        assert!(!filter.contains(b"a"));
+        filter.insert(b"a");
+        assert!(filter.contains(b"a"));
+        assert!(!filter.contains(b"b"));
+        filter.insert(b"b");
+        assert!(filter.contains(b"b"));
+    }
+
+    #[test]
+    fn test_false_positive_rate() {
+        let mut filter = BloomFilter::new(4096, 0.01);
+        let capacity = filter.capacity();
+        assert!(capacity > 3000);
+
+        for i in 0..capacity {
+            filter.insert(format!("in-{i}").as_bytes());
+        }
+
+        let trials = 10_000;
+        let false_positives = (0..trials)
+            .filter(|i| filter.contains(format!("out-{i}").as_bytes()))
+            .count();
+        assert!(false_positives < trials / 50, "{false_positives}");
+    }
+}
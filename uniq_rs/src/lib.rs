@@ -1,12 +1,19 @@
-use clap::Parser;
+mod bloom;
+
+use bloom::BloomFilter;
+use clap::{ArgGroup, Parser};
+use regex::bytes::Regex;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
-use std::fs::File;
-use std::io::{self, BufRead, BufReader, Write};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
+#[command(group(ArgGroup::new("whole_input").args(["global", "top"])))]
 pub struct Args {
     /// Input file
     #[arg(default_value = "-")]
@@ -18,60 +25,503 @@ pub struct Args {
     /// Precede each output line with the count of the number of times the line occurred in the input
     #[arg(short, long)]
     count: bool,
+
+    /// Avoid comparing the first N fields
+    #[arg(short = 'f', long, value_name = "N", default_value_t = 0)]
+    skip_fields: usize,
+
+    /// Compare only the text matched by the first capture group of REGEX (or the whole match)
+    #[arg(long, value_name = "REGEX", conflicts_with_all = ["skip_fields", "key_field"])]
+    key_regex: Option<Regex>,
+
+    /// Compare only field N, as split by --delimiter
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..), conflicts_with = "skip_fields")]
+    key_field: Option<u64>,
+
+    /// Field delimiter for --key-field [default: tab]
+    #[arg(
+        short = 't',
+        long,
+        value_name = "DELIM",
+        default_value = "\t",
+        hide_default_value = true,
+        value_parser = parse_delimiter
+    )]
+    delimiter: u8,
+
+    /// Avoid comparing the first N characters
+    #[arg(short, long, value_name = "N", default_value_t = 0)]
+    skip_chars: usize,
+
+    /// Compare no more than N characters in lines
+    #[arg(short = 'w', long, value_name = "N")]
+    check_chars: Option<usize>,
+
+    /// Ignore differences in case when comparing
+    #[arg(short, long)]
+    ignore_case: bool,
+
+    /// Line delimiter is NUL, not newline
+    #[arg(short, long)]
+    zero_terminated: bool,
+
+    /// Remove duplicate lines anywhere in the input, keeping the first occurrence
+    #[arg(long, conflicts_with = "count")]
+    global: bool,
+
+    /// Print the N most frequent lines in the whole input with their counts; N lines are kept even past --max-memory
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..), conflicts_with = "global")]
+    top: Option<u64>,
+
+    /// Memory limit for --global and --top (e.g. 512M); beyond it results are approximate
+    ///
+    /// --global keeps the lines it has seen in up to half of SIZE, then
+    /// switches to a Bloom filter of the other half, so memory stays within
+    /// SIZE. From then on a line not seen before is dropped as a duplicate with
+    /// probability --false-positive-rate, which rises once the filter holds more
+    /// lines than it was sized for; the number is printed when it switches.
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, requires = "whole_input")]
+    max_memory: Option<usize>,
+
+    /// False-positive rate of the Bloom filter used once --max-memory is reached
+    #[arg(long, value_name = "RATE", default_value_t = 0.01, value_parser = parse_rate)]
+    false_positive_rate: f64,
 }
 
+/// Approximate heap cost of one key in the exact set, besides its bytes.
+const ENTRY_OVERHEAD: usize = 4 * std::mem::size_of::<usize>();
+
 pub fn run(args: Args) -> MyResult<()> {
+    if let Some(output_file) = args.output_file.as_deref() {
+        if is_same_file(&args.input_file, output_file) {
+            return Err(format!("{}: input file is output file", output_file).into());
+        }
+    }
+
     let mut read_file =
         read_open(&args.input_file).map_err(|err| format!("{}: {}", args.input_file, err))?;
 
+    let output_name = args.output_file.as_deref().unwrap_or("standard output");
     let mut write_file = write_open(args.output_file.as_deref())
-        .map_err(|err| format!("{}: {}", args.input_file, err))?;
+        .map_err(|err| format!("{}: {}", output_name, err))?;
+
+    if args.global {
+        run_global(&args, &mut read_file, &mut write_file)?;
+    } else if let Some(n) = args.top {
+        run_top(&args, n as usize, &mut read_file, &mut write_file)?;
+    } else {
+        run_adjacent(&args, &mut read_file, &mut write_file)?;
+    }
+
+    write_file.flush()?;
+    Ok(())
+}
 
-    let mut print = |count: u64, text: &str| -> MyResult<()> {
+/// Prints one line per run of adjacent lines with equal keys.
+fn run_adjacent(
+    args: &Args,
+    read_file: &mut impl BufRead,
+    write_file: &mut impl Write,
+) -> MyResult<()> {
+    let delimiter = line_delimiter(args);
+    let mut print = |count: u64, text: &[u8]| -> MyResult<()> {
         if count > 0 {
-            if args.count {
-                write!(write_file, "{:>4} {}", count, text)?;
-            } else {
-                write!(write_file, "{}", text)?;
-            }
+            write_record(write_file, args.count.then_some(count), text, delimiter)?;
         };
         Ok(())
     };
 
-    let mut line = String::new();
-    let mut previous = String::new();
+    let mut line = Vec::new();
+    let mut previous = Vec::new();
     let mut count: u64 = 0;
 
-    loop {
-        let bytes = read_file.read_line(&mut line)?;
-        if bytes == 0 {
-            break;
-        }
-
-        if line.trim_end() != previous.trim_end() {
+    while read_record(read_file, delimiter, &mut line)? {
+        if count == 0 || compare_key(args, &line) != compare_key(args, &previous) {
             print(count, &previous)?;
-            previous = line.clone();
+            std::mem::swap(&mut previous, &mut line);
             count = 0;
         }
 
         count += 1;
-        line.clear();
     }
 
     print(count, &previous)?;
     Ok(())
 }
 
-fn read_open(filename: &str) -> MyResult<Box<dyn BufRead>> {
-    match filename {
-        "-" => Ok(Box::new(BufReader::new(io::stdin()))),
-        _ => Ok(Box::new(BufReader::new(File::open(filename)?))),
+/// Prints the first occurrence of every key in the input, in input order.
+///
+/// Keys are kept in a hash set until it grows past half of `--max-memory`.
+/// The set is then replaced by a Bloom filter taking the other half, freeing
+/// each key as it is moved, so the two together stay within the limit. From
+/// then on a line whose key was never seen may be dropped as a duplicate
+/// with probability `--false-positive-rate`, which rises once the filter
+/// holds more keys than its capacity (see [`BloomFilter`]).
+fn run_global(
+    args: &Args,
+    read_file: &mut impl BufRead,
+    write_file: &mut impl Write,
+) -> MyResult<()> {
+    let delimiter = line_delimiter(args);
+    let mut seen: HashSet<Vec<u8>> = HashSet::new();
+    let mut used: usize = 0;
+    let mut filter: Option<BloomFilter> = None;
+    let mut line = Vec::new();
+
+    while read_record(read_file, delimiter, &mut line)? {
+        let key = compare_key(args, &line);
+        let is_new = match filter.as_mut() {
+            Some(filter) => {
+                let is_new = !filter.contains(&key);
+                if is_new {
+                    filter.insert(&key);
+                }
+                is_new
+            }
+            None => {
+                let is_new = !seen.contains(key.as_ref());
+                if is_new {
+                    used += key.len() + ENTRY_OVERHEAD;
+                    seen.insert(key.into_owned());
+                }
+                is_new
+            }
+        };
+
+        if let Some(max_memory) = args
+            .max_memory
+            .filter(|&max| filter.is_none() && used > max / 2)
+        {
+            let distinct = seen.len();
+            let mut spilled =
+                BloomFilter::new(max_memory - max_memory / 2, args.false_positive_rate);
+            for key in std::mem::take(&mut seen) {
+                spilled.insert(&key);
+            }
+            eprintln!(
+                "uniq_rs: memory limit reached after {} distinct lines, \
+                 switching to a Bloom filter (false-positive rate {} for up to {} lines)",
+                distinct,
+                args.false_positive_rate,
+                spilled.capacity()
+            );
+            filter = Some(spilled);
+        }
+
+        if is_new {
+            write_record(write_file, None, &line, delimiter)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// A distinct key seen by `--top`, with the first line that produced it.
+struct Tally {
+    /// The count, plus the number of decrements made before the key was
+    /// added; the key's count is this less the decrements made so far
+    count: u64,
+    first_seen: u64,
+    line: Vec<u8>,
+}
+
+/// Prints the `n` most frequent keys in the input with their counts, most
+/// frequent first and ties in order of first occurrence.
+///
+/// Counts are exact while the table fits in `--max-memory`. Once it is full,
+/// the table keeps its size and continues with the Misra-Gries algorithm: a
+/// key that is not already counted decrements every counter instead of being
+/// added. Any key occurring more than `m / (k + 1)` times in the `m` records
+/// read after that point is guaranteed to be kept, where `k` is the table
+/// size, and its printed count is at most that much lower than the true one.
+///
+/// Decrementing every counter only raises an offset that is subtracted from
+/// them all, and the table is swept only when a counter reaches zero, so a
+/// record costs constant time apart from the sweeps, each of which removes
+/// at least one key. The table keeps room for at least `n` keys, even if
+/// that takes more than `--max-memory`.
+fn run_top(
+    args: &Args,
+    n: usize,
+    read_file: &mut impl BufRead,
+    write_file: &mut impl Write,
+) -> MyResult<()> {
+    let delimiter = line_delimiter(args);
+    let mut tallies: HashMap<Vec<u8>, Tally> = HashMap::new();
+    let mut used: usize = 0;
+    let mut capacity: Option<usize> = None;
+    // Once the table is full: how many times every counter was decremented,
+    // and how many keys there are for each stored count
+    let mut offset: u64 = 0;
+    let mut keys_at: HashMap<u64, usize> = HashMap::new();
+    let mut records: u64 = 0;
+    let mut line = Vec::new();
+
+    while read_record(read_file, delimiter, &mut line)? {
+        records += 1;
+        let key = compare_key(args, &line);
+        if let Some(tally) = tallies.get_mut(key.as_ref()) {
+            if capacity.is_some() {
+                move_key(&mut keys_at, tally.count, tally.count + 1);
+            }
+            tally.count += 1;
+            continue;
+        }
+
+        if capacity.is_some_and(|capacity| tallies.len() >= capacity) {
+            offset += 1;
+            if keys_at.remove(&offset).is_some() {
+                tallies.retain(|_, tally| tally.count > offset);
+            }
+            continue;
+        }
+
+        used += key.len() + line.len() + ENTRY_OVERHEAD;
+        if capacity.is_some() {
+            move_key(&mut keys_at, 0, offset + 1);
+        }
+        tallies.insert(
+            key.into_owned(),
+            Tally {
+                count: offset + 1,
+                first_seen: records,
+                line: line.clone(),
+            },
+        );
+
+        if capacity.is_none() && args.max_memory.is_some_and(|max| used > max) {
+            capacity = Some(tallies.len().max(n));
+            for tally in tallies.values() {
+                move_key(&mut keys_at, 0, tally.count);
+            }
+            eprintln!(
+                "uniq_rs: memory limit reached after {} distinct lines, \
+                 counts from line {} on are approximate",
+                tallies.len(),
+                records + 1
+            );
+        }
+    }
+
+    let mut top: Vec<Tally> = tallies.into_values().collect();
+    top.sort_by(|a, b| b.count.cmp(&a.count).then(a.first_seen.cmp(&b.first_seen)));
+    for tally in top.into_iter().take(n) {
+        write_record(
+            write_file,
+            Some(tally.count - offset),
+            &tally.line,
+            delimiter,
+        )?;
     }
+
+    Ok(())
+}
+
+/// Moves a key of `--top` from stored count `from` to `to` in `keys_at`,
+/// where 0 is no count at all.
+fn move_key(keys_at: &mut HashMap<u64, usize>, from: u64, to: u64) {
+    if let Some(keys) = keys_at.get_mut(&from) {
+        *keys -= 1;
+        if *keys == 0 {
+            keys_at.remove(&from);
+        }
+    }
+    *keys_at.entry(to).or_insert(0) += 1;
+}
+
+fn line_delimiter(args: &Args) -> u8 {
+    if args.zero_terminated {
+        b'\0'
+    } else {
+        b'\n'
+    }
+}
+
+/// Reads the next record into `buf` without its delimiter.
+///
+/// A final record that is not followed by a delimiter is still returned, so it
+/// is compared and printed like any other. Returns `false` at end of input.
+fn read_record(read_file: &mut impl BufRead, delimiter: u8, buf: &mut Vec<u8>) -> io::Result<bool> {
+    buf.clear();
+    if read_file.read_until(delimiter, buf)? == 0 {
+        return Ok(false);
+    }
+    if buf.last() == Some(&delimiter) {
+        buf.pop();
+    }
+    Ok(true)
+}
+
+/// Writes `line` followed by `delimiter`, preceded by `count` in the same
+/// 7-wide column that GNU uniq uses.
+fn write_record(
+    write_file: &mut impl Write,
+    count: Option<u64>,
+    line: &[u8],
+    delimiter: u8,
+) -> io::Result<()> {
+    if let Some(count) = count {
+        write!(write_file, "{:>7} ", count)?;
+    }
+    write_file.write_all(line)?;
+    write_file.write_all(&[delimiter])
+}
+
+/// Returns the part of `line` that takes part in the comparison.
+///
+/// Lines are compared byte for byte, as GNU uniq does. The key is first
+/// narrowed by `--key-regex`, `--key-field` or `--skip-fields`, then by
+/// `--skip-chars` and `--check-chars` (counted in bytes), and finally folded
+/// by `--ignore-case` (ASCII only). Lines where the regex does not match or
+/// the field is missing have an empty key.
+fn compare_key<'a>(args: &Args, line: &'a [u8]) -> Cow<'a, [u8]> {
+    let is_blank = |b: &u8| *b == b' ' || *b == b'\t';
+
+    let mut key = line;
+    if let Some(re) = &args.key_regex {
+        key = re
+            .captures(key)
+            .and_then(|caps| caps.get(1).or_else(|| caps.get(0)))
+            .map_or(&[], |m| m.as_bytes());
+    } else if let Some(n) = args.key_field {
+        key = key
+            .split(|b| *b == args.delimiter)
+            .nth(n as usize - 1)
+            .unwrap_or(&[]);
+    } else {
+        // A field is a run of blanks followed by a run of non-blanks.
+        for _ in 0..args.skip_fields {
+            let start = key.iter().position(|b| !is_blank(b)).unwrap_or(key.len());
+            key = &key[start..];
+            let end = key.iter().position(is_blank).unwrap_or(key.len());
+            key = &key[end..];
+        }
+    }
+
+    key = &key[args.skip_chars.min(key.len())..];
+    if let Some(n) = args.check_chars {
+        key = &key[..n.min(key.len())];
+    }
+
+    if args.ignore_case {
+        Cow::Owned(key.to_ascii_lowercase())
+    } else {
+        Cow::Borrowed(key)
+    }
+}
+
+/// Parses a byte count with an optional K, M or G suffix.
+fn parse_size(size: &str) -> Result<usize, String> {
+    let (digits, multiplier) = match size.char_indices().last() {
+        Some((i, 'K' | 'k')) => (&size[..i], 1 << 10),
+        Some((i, 'M' | 'm')) => (&size[..i], 1 << 20),
+        Some((i, 'G' | 'g')) => (&size[..i], 1 << 30),
+        _ => (size, 1),
+    };
+    digits
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(|| format!("invalid size '{}'", size))
+}
+
+fn parse_rate(rate: &str) -> Result<f64, String> {
+    match rate.parse::<f64>() {
+        Ok(rate) if rate > 0.0 && rate < 1.0 => Ok(rate),
+        _ => Err(format!("'{}' is not between 0 and 1", rate)),
+    }
+}
+
+fn parse_delimiter(delimiter: &str) -> Result<u8, String> {
+    match delimiter.as_bytes() {
+        [byte] => Ok(*byte),
+        _ => Err(format!("'{}' is not a single byte", delimiter)),
+    }
+}
+
+/// Returns `true` if `output` already exists and is the same file as `input`,
+/// in which case creating the output would truncate the input before it is read.
+fn is_same_file(input: &str, output: &str) -> bool {
+    if input == "-" {
+        return false;
+    }
+    match (fs::metadata(input), fs::metadata(output)) {
+        #[cfg(unix)]
+        (Ok(input), Ok(output)) => {
+            use std::os::unix::fs::MetadataExt;
+            input.dev() == output.dev() && input.ino() == output.ino()
+        }
+        #[cfg(not(unix))]
+        (Ok(_), Ok(_)) => fs::canonicalize(input).ok() == fs::canonicalize(output).ok(),
+        _ => false,
+    }
+}
+
+fn read_open(filename: &str) -> MyResult<Box<dyn BufRead>> {
+    let reader: Box<dyn BufRead> = match filename {
+        "-" => Box::new(BufReader::new(io::stdin())),
+        _ => Box::new(BufReader::new(File::open(filename)?)),
+    };
+    Ok(Box::new(Named::new(filename, reader)))
 }
 
 fn write_open(filename: Option<&str>) -> MyResult<Box<dyn Write>> {
-    match filename {
-        Some(name) => Ok(Box::new(File::create(name)?)),
-        None => Ok(Box::new(io::stdout())),
+    let writer: Box<dyn Write> = match filename {
+        Some(name) => Box::new(File::create(name)?),
+        None => Box::new(io::stdout()),
+    };
+    let name = filename.unwrap_or("standard output");
+    Ok(Box::new(Named::new(name, BufWriter::new(writer))))
+}
+
+/// Prefixes I/O errors with the name of the file they happened on, so read
+/// and write failures can be told apart.
+struct Named<T> {
+    name: String,
+    inner: T,
+}
+
+impl<T> Named<T> {
+    fn new(name: &str, inner: T) -> Named<T> {
+        Named {
+            name: name.to_string(),
+            inner,
+        }
+    }
+}
+
+fn annotate(name: &str, err: io::Error) -> io::Error {
+    io::Error::new(err.kind(), format!("{}: {}", name, err))
+}
+
+impl<T: Read> Read for Named<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner
+            .read(buf)
+            .map_err(|err| annotate(&self.name, err))
+    }
+}
+
+impl<T: BufRead> BufRead for Named<T> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner
+            .fill_buf()
+            .map_err(|err| annotate(&self.name, err))
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt)
+    }
+}
+
+impl<T: Write> Write for Named<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner
+            .write(buf)
+            .map_err(|err| annotate(&self.name, err))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush().map_err(|err| annotate(&self.name, err))
     }
 }
@@ -617,3 +617,401 @@ fn t6_outfile_count() -> Result<()> {
 fn t6_stdin_outfile_count() -> Result<()> {
     run_stdin_outfile_count(&T6)
 }
+
+// --------------------------------------------------
+fn run_opts(input: &str, opts: &[&str], expected_file: &str) -> Result<()> {
+    let expected = fs::read_to_string(expected_file)?;
+    let output = Command::cargo_bin(PRG)?
+        .arg(input)
+        .args(opts)
+        .output()
+        .expect("fail");
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    assert_eq!(stdout, expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+const LOG: &str = "tests/inputs/log.txt";
+
+#[test]
+fn skip_fields() -> Result<()> {
+    run_opts(LOG, &["-f", "2"], "tests/expected/log.txt.f2.out")
+}
+
+#[test]
+fn skip_fields_count() -> Result<()> {
+    run_opts(
+        LOG,
+        &["-c", "--skip-fields", "2"],
+        "tests/expected/log.txt.f2.c.out",
+    )
+}
+
+#[test]
+fn skip_fields_ignore_case() -> Result<()> {
+    run_opts(LOG, &["-f", "2", "-i"], "tests/expected/log.txt.f2i.out")
+}
+
+#[test]
+fn skip_fields_ignore_case_count() -> Result<()> {
+    run_opts(
+        LOG,
+        &["-c", "-f", "2", "--ignore-case"],
+        "tests/expected/log.txt.f2i.c.out",
+    )
+}
+
+#[test]
+fn skip_chars() -> Result<()> {
+    run_opts(LOG, &["-s", "20"], "tests/expected/log.txt.s20.out")
+}
+
+#[test]
+fn skip_chars_count() -> Result<()> {
+    run_opts(
+        LOG,
+        &["-c", "--skip-chars", "20"],
+        "tests/expected/log.txt.s20.c.out",
+    )
+}
+
+#[test]
+fn skip_chars_check_chars() -> Result<()> {
+    run_opts(
+        LOG,
+        &["-s", "20", "-w", "5"],
+        "tests/expected/log.txt.s20w5.out",
+    )
+}
+
+#[test]
+fn skip_chars_check_chars_count() -> Result<()> {
+    run_opts(
+        LOG,
+        &["-c", "-s", "20", "--check-chars", "5"],
+        "tests/expected/log.txt.s20w5.c.out",
+    )
+}
+
+#[test]
+fn skip_fields_check_chars() -> Result<()> {
+    run_opts(
+        LOG,
+        &["-f", "3", "-w", "10"],
+        "tests/expected/log.txt.f3w10.out",
+    )
+}
+
+#[test]
+fn skip_fields_check_chars_count() -> Result<()> {
+    run_opts(
+        LOG,
+        &["-c", "-f", "3", "-w", "10"],
+        "tests/expected/log.txt.f3w10.c.out",
+    )
+}
+
+// --------------------------------------------------
+const EVENTS: &str = "tests/inputs/events.txt";
+
+#[test]
+fn global() -> Result<()> {
+    run_opts(
+        EVENTS,
+        &["--global"],
+        "tests/expected/events.txt.global.out",
+    )
+}
+
+#[test]
+fn global_ignore_case() -> Result<()> {
+    run_opts(
+        EVENTS,
+        &["--global", "-i"],
+        "tests/expected/events.txt.global.i.out",
+    )
+}
+
+#[test]
+fn global_max_memory() -> Result<()> {
+    let expected = fs::read_to_string("tests/expected/events.txt.global.out")?;
+    Command::cargo_bin(PRG)?
+        .args([EVENTS, "--global", "--max-memory", "1K"])
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+#[test]
+fn global_max_memory_spills() -> Result<()> {
+    let expected = fs::read_to_string("tests/expected/events.txt.global.out")?;
+    Command::cargo_bin(PRG)?
+        .args([EVENTS, "--global", "--max-memory", "64"])
+        .assert()
+        .success()
+        .stdout(expected)
+        .stderr(predicate::str::contains("switching to a Bloom filter"));
+    Ok(())
+}
+
+#[test]
+fn dies_global_count() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args([EVENTS, "--global", "--count"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
+#[test]
+fn dies_bad_max_memory() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args([EVENTS, "--global", "--max-memory", "lots"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid size 'lots'"));
+    Ok(())
+}
+
+// --------------------------------------------------
+const ORDERS: &str = "tests/inputs/orders.csv";
+
+#[test]
+fn key_field() -> Result<()> {
+    run_opts(
+        ORDERS,
+        &["--key-field", "1", "-t", ","],
+        "tests/expected/orders.csv.key_field1.out",
+    )
+}
+
+#[test]
+fn key_field_global() -> Result<()> {
+    run_opts(
+        ORDERS,
+        &["--key-field", "1", "--delimiter", ",", "--global"],
+        "tests/expected/orders.csv.key_field1.global.out",
+    )
+}
+
+#[test]
+fn key_regex_count() -> Result<()> {
+    run_opts(
+        ORDERS,
+        &["--key-regex", "^[^,]*,([^,]*),", "-c"],
+        "tests/expected/orders.csv.key_regex_date.c.out",
+    )
+}
+
+#[test]
+fn key_regex_global() -> Result<()> {
+    run_opts(
+        ORDERS,
+        &["--key-regex", "[a-z]+", "--global"],
+        "tests/expected/orders.csv.key_regex_item.global.out",
+    )
+}
+
+#[test]
+fn dies_bad_key_regex() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args([ORDERS, "--key-regex", "("])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid value '('"));
+    Ok(())
+}
+
+#[test]
+fn dies_key_field_zero() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args([ORDERS, "--key-field", "0"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid value '0'"));
+    Ok(())
+}
+
+#[test]
+fn dies_key_field_skip_fields() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args([ORDERS, "--key-field", "1", "-f", "1"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn trailing_space_is_significant() -> Result<()> {
+    run_opts(
+        "tests/inputs/space.txt",
+        &[],
+        "tests/expected/space.txt.out",
+    )
+}
+
+#[test]
+fn newline_terminated_with_nul() -> Result<()> {
+    run_opts("tests/inputs/nul.txt", &[], "tests/expected/nul.txt.out")
+}
+
+#[test]
+fn zero_terminated() -> Result<()> {
+    run_opts(
+        "tests/inputs/nul.txt",
+        &["-z"],
+        "tests/expected/nul.txt.z.out",
+    )
+}
+
+#[test]
+fn zero_terminated_count() -> Result<()> {
+    run_opts(
+        "tests/inputs/nul.txt",
+        &["--zero-terminated", "-c"],
+        "tests/expected/nul.txt.zc.out",
+    )
+}
+
+#[test]
+fn invalid_utf8() -> Result<()> {
+    let expected = fs::read("tests/expected/latin1.txt.out")?;
+    Command::cargo_bin(PRG)?
+        .arg("tests/inputs/latin1.txt")
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+const ACCESS: &str = "tests/inputs/access.log";
+
+#[test]
+fn top() -> Result<()> {
+    run_opts(
+        ACCESS,
+        &["--top", "2"],
+        "tests/expected/access.log.top2.out",
+    )
+}
+
+#[test]
+fn top_more_than_distinct() -> Result<()> {
+    run_opts(
+        ACCESS,
+        &["--top", "10"],
+        "tests/expected/access.log.top10.out",
+    )
+}
+
+#[test]
+fn top_skip_fields() -> Result<()> {
+    run_opts(
+        ACCESS,
+        &["--top", "3", "-f", "1"],
+        "tests/expected/access.log.top3.f1.out",
+    )
+}
+
+#[test]
+fn top_max_memory() -> Result<()> {
+    // The most frequent line still wins, but its count becomes a lower bound.
+    Command::cargo_bin(PRG)?
+        .args([ACCESS, "--top", "1", "-f", "1", "--max-memory", "100"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(
+            "^ +[0-9]+ 10.0.0.1 GET /index.html\n$",
+        )?)
+        .stderr(predicate::str::contains("approximate"));
+    Ok(())
+}
+
+#[test]
+fn top_max_memory_many_keys() -> Result<()> {
+    // A third of the lines are the same; the rest are all distinct and keep
+    // pushing counters down to zero once the table is full.
+    let input: String = (0..30_000)
+        .map(|i| match i % 3 {
+            0 => "hot\n".to_string(),
+            _ => format!("cold {}\n", i),
+        })
+        .collect();
+    Command::cargo_bin(PRG)?
+        .args(["--top", "1", "--max-memory", "1K"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match("^ +[0-9]+ hot\n$")?);
+    Ok(())
+}
+
+#[test]
+fn dies_top_global() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args([ACCESS, "--top", "1", "--global"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
+#[test]
+fn dies_max_memory_without_mode() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args([ACCESS, "--max-memory", "1M"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "required arguments were not provided",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_outfile() -> Result<()> {
+    let bad = format!("{}/out.txt", gen_bad_file());
+    let expected = format!("^{bad}: .* [(]os error [23][)]");
+    Command::cargo_bin(PRG)?
+        .args([ONE.input, &bad])
+        .assert()
+        .failure()
+        .stderr(predicate::str::is_match(expected)?);
+    Ok(())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn dies_write_error() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args([THREE.input, "/dev/full"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::starts_with("/dev/full: "));
+    Ok(())
+}
+
+#[test]
+fn dies_outfile_is_infile() -> Result<()> {
+    let input = fs::read_to_string(THREE.input)?;
+    let file = NamedTempFile::new()?;
+    fs::write(file.path(), &input)?;
+    let path = file.path().to_str().unwrap();
+
+    Command::cargo_bin(PRG)?
+        .args([path, path])
+        .assert()
+        .failure()
+        .stderr(format!("{path}: input file is output file\n"));
+    assert_eq!(fs::read_to_string(path)?, input);
+    Ok(())
+}